@@ -1,11 +1,15 @@
 use mpi::{
     collective::{CommunicatorCollectives, Root},
-    datatype::PartitionMut,
-    topology::Rank,
+    datatype::{Partition, PartitionMut},
+    topology::{Communicator, Rank},
 };
 use quick_error::quick_error;
 use serde::{Deserialize, Serialize};
-use std::{convert::TryInto, num::TryFromIntError, ops::Range};
+use std::{
+    convert::TryInto,
+    num::TryFromIntError,
+    ops::{AddAssign, Range},
+};
 
 quick_error! {
     #[derive(Debug)]
@@ -21,6 +25,20 @@ quick_error! {
     }
 }
 
+/// Payload bytes sent and received by this process
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Traffic {
+    pub sent: usize,
+    pub received: usize,
+}
+
+impl AddAssign for Traffic {
+    fn add_assign(&mut self, other: Self) {
+        self.sent += other.sent;
+        self.received += other.received;
+    }
+}
+
 pub fn bincode_all_gather_varcount<Comm, T>(
     comm: Comm,
     send: &T,
@@ -33,32 +51,160 @@ where
     let serialized = bincode::serialize(send)?;
     let local_count: i32 = serialized.len().try_into()?;
     let mut counts = vec![0i32; size];
-    let mut displacements = vec![0i32; size];
     comm.all_gather_into(&local_count, &mut counts[..]);
-    {
-        let mut place = 0i32;
-        for (i, c) in counts.iter().enumerate() {
-            displacements[i] = place;
-            place += c;
-        }
-    }
+    let displacements = displacements(&counts);
     let buffer_size: usize = counts.iter().map(|n| *n as usize).sum();
     let mut receive_buffer = vec![0u8; buffer_size];
     let mut partition = PartitionMut::new(&mut receive_buffer[..], &counts[..], &displacements[..]);
     comm.all_gather_varcount_into(&serialized[..], &mut partition);
+    deserialize_partitions(&receive_buffer, &counts, &displacements)
+}
+
+/// Send the items `send[r]` to each process `r` and return items received
+/// from all processes in the order of ranks.
+///
+/// Only sizes are exchanged with processes having no items to send or
+/// receive, so items travel to neighbors only.
+pub fn bincode_all_to_all_varcount<Comm, T>(
+    comm: Comm,
+    send: &[Vec<T>],
+) -> Result<(Vec<T>, Traffic), CommunicationError>
+where
+    Comm: CommunicatorCollectives,
+    T: Serialize + for<'a> Deserialize<'a>,
+{
+    let size = comm.size() as usize;
+    assert_eq!(send.len(), size);
+    let mut send_buffer = Vec::new();
+    let mut send_counts = Vec::with_capacity(size);
+    for items in send {
+        let serialized = if items.is_empty() {
+            Vec::new()
+        } else {
+            bincode::serialize(items)?
+        };
+        send_counts.push(serialized.len().try_into()?);
+        send_buffer.extend(serialized);
+    }
+    let send_displacements = displacements(&send_counts);
+    let mut receive_counts = vec![0i32; size];
+    comm.all_to_all_into(&send_counts[..], &mut receive_counts[..]);
+    let receive_displacements = displacements(&receive_counts);
+    let buffer_size: usize = receive_counts.iter().map(|n| *n as usize).sum();
+    let mut receive_buffer = vec![0u8; buffer_size];
+    {
+        let partition = Partition::new(&send_buffer[..], &send_counts[..], &send_displacements[..]);
+        let mut receive_partition = PartitionMut::new(
+            &mut receive_buffer[..],
+            &receive_counts[..],
+            &receive_displacements[..],
+        );
+        comm.all_to_all_varcount_into(&partition, &mut receive_partition);
+    }
+    let rank = comm.rank() as usize;
+    let others = |counts: &[i32]| -> usize {
+        counts
+            .iter()
+            .enumerate()
+            .filter(|(r, _)| *r != rank)
+            .map(|(_, c)| *c as usize)
+            .sum()
+    };
+    let traffic = Traffic {
+        sent: others(&send_counts),
+        received: others(&receive_counts),
+    };
+    let mut received = Vec::new();
+    for (d, c) in receive_displacements.iter().zip(receive_counts.iter()) {
+        if *c > 0 {
+            let d = *d as usize;
+            let c = *c as usize;
+            let items: Vec<T> = bincode::deserialize(&receive_buffer[d..d + c])?;
+            received.extend(items);
+        }
+    }
+    Ok((received, traffic))
+}
+
+/// Gather items of all processes to the root.
+///
+/// Return items in the order of ranks in the root, and `None` in other
+/// processes.
+pub fn bincode_gather_varcount<Comm, T>(
+    comm: Comm,
+    root: Rank,
+    send: &T,
+) -> Result<(Option<Vec<T>>, Traffic), CommunicationError>
+where
+    Comm: Communicator,
+    T: Serialize + for<'a> Deserialize<'a>,
+{
+    let serialized = bincode::serialize(send)?;
+    let local_count: i32 = serialized.len().try_into()?;
+    let root_process = comm.process_at_rank(root);
+    if comm.rank() == root {
+        let size = comm.size() as usize;
+        let mut counts = vec![0i32; size];
+        root_process.gather_into_root(&local_count, &mut counts[..]);
+        let displacements = displacements(&counts);
+        let buffer_size: usize = counts.iter().map(|n| *n as usize).sum();
+        let mut receive_buffer = vec![0u8; buffer_size];
+        let mut partition =
+            PartitionMut::new(&mut receive_buffer[..], &counts[..], &displacements[..]);
+        root_process.gather_varcount_into_root(&serialized[..], &mut partition);
+        let traffic = Traffic {
+            sent: 0,
+            received: buffer_size - serialized.len(),
+        };
+        let gathered = deserialize_partitions(&receive_buffer, &counts, &displacements)?;
+        Ok((Some(gathered), traffic))
+    } else {
+        root_process.gather_into(&local_count);
+        root_process.gather_varcount_into(&serialized[..]);
+        let traffic = Traffic {
+            sent: serialized.len(),
+            received: 0,
+        };
+        Ok((None, traffic))
+    }
+}
+
+fn displacements(counts: &[i32]) -> Vec<i32> {
+    counts
+        .iter()
+        .scan(0i32, |place, c| {
+            let displacement = *place;
+            *place += c;
+            Some(displacement)
+        })
+        .collect()
+}
+
+fn deserialize_partitions<T>(
+    buffer: &[u8],
+    counts: &[i32],
+    displacements: &[i32],
+) -> Result<Vec<T>, CommunicationError>
+where
+    T: for<'a> Deserialize<'a>,
+{
     displacements
         .iter()
         .zip(counts.iter())
         .map(|(d, c)| {
             let d = *d as usize;
             let c = *c as usize;
-            let single = &receive_buffer[d..d + c];
+            let single = &buffer[d..d + c];
             bincode::deserialize(single).map_err(CommunicationError::Bincode)
         })
         .collect()
 }
 
-pub fn bincode_broadcast<R, T>(rank: Rank, root: R, item: &mut T) -> Result<(), CommunicationError>
+pub fn bincode_broadcast<R, T>(
+    rank: Rank,
+    root: R,
+    item: &mut T,
+) -> Result<Traffic, CommunicationError>
 where
     R: Root,
     T: Serialize + for<'a> Deserialize<'a>,
@@ -68,14 +214,21 @@ where
         let mut length = serialized.len();
         root.broadcast_into(&mut length);
         root.broadcast_into(&mut serialized[..]);
+        Ok(Traffic {
+            sent: length,
+            received: 0,
+        })
     } else {
         let mut length = 0usize;
         root.broadcast_into(&mut length);
         let mut buffer = vec![0u8; length];
         root.broadcast_into(&mut buffer[..]);
         *item = bincode::deserialize(&buffer[..])?;
+        Ok(Traffic {
+            sent: 0,
+            received: length,
+        })
     }
-    Ok(())
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
@@ -105,6 +258,27 @@ impl Division {
     }
 }
 
+/// Run `f` with the world communicator in tests.
+///
+/// MPI is initialized once for all tests, and tests running in parallel
+/// threads take turns.
+#[cfg(test)]
+pub(crate) fn with_world<F, T>(f: F) -> T
+where
+    F: FnOnce(mpi::topology::SystemCommunicator) -> T,
+{
+    use mpi::{environment::Universe, Threading};
+    use std::sync::Mutex;
+    static UNIVERSE: Mutex<Option<Universe>> = Mutex::new(None);
+    let mut universe = UNIVERSE.lock().unwrap_or_else(|e| e.into_inner());
+    let universe = universe.get_or_insert_with(|| {
+        mpi::initialize_with_threading(Threading::Serialized)
+            .expect("MPI initialized twice")
+            .0
+    });
+    f(universe.world())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
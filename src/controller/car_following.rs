@@ -0,0 +1,300 @@
+//! Car following models
+//!
+//! A car following model calculates the acceleration of a car from the
+//! situation between the car and its front object. Built-in models are
+//! variants of `DrivingModel`, other models can be registered to a
+//! `CarFollowingRegistry` by name and referred with `DrivingModel::Custom`.
+//! Since only the name and parameters of a custom model are stored in the
+//! model, all processes must register the same models. Models of all
+//! profiles of cars are built once, when the update controller is created.
+
+use crate::model::stateless::{
+    self,
+    car::{CustomDrivingModel, DrivingModel, IntelligentDriverModel, NormalDrivingModel},
+};
+use quick_error::quick_error;
+use std::{collections::HashMap, fmt, rc::Rc};
+
+quick_error! {
+    #[derive(Debug)]
+    pub enum CarFollowingError {
+        Unregistered(name: String) {
+            display("car following model {} is not registered", name)
+        }
+    }
+}
+
+/// Situation between a car and its front object
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FollowingSituation {
+    pub velocity: f64,
+    pub acceleration: f64,
+    /// Distance to the front object
+    pub gap: f64,
+    /// Velocity of the front object
+    pub leader_velocity: f64,
+    /// Speed limit of the lane, models should treat it as a cap of the
+    /// desired velocity
+    pub speed_limit: f64,
+}
+
+pub trait CarFollowingModel {
+    fn acceleration(&self, situation: &FollowingSituation) -> f64;
+}
+
+/// Construct a custom car following model from its parameters
+pub type CarFollowingModelConstructor = fn(&[f64]) -> Box<dyn CarFollowingModel>;
+
+#[derive(Clone, Default)]
+pub struct CarFollowingRegistry {
+    constructors: HashMap<String, CarFollowingModelConstructor>,
+}
+
+impl fmt::Debug for CarFollowingRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.constructors.keys()).finish()
+    }
+}
+
+impl CarFollowingRegistry {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn register<S: Into<String>>(
+        &mut self,
+        name: S,
+        constructor: CarFollowingModelConstructor,
+    ) {
+        self.constructors.insert(name.into(), constructor);
+    }
+
+    pub fn is_registered(&self, name: &str) -> bool {
+        self.constructors.contains_key(name)
+    }
+
+    pub fn model(
+        &self,
+        driving_model: &DrivingModel,
+    ) -> Result<Rc<dyn CarFollowingModel>, CarFollowingError> {
+        Ok(match driving_model {
+            DrivingModel::Normal(model) => Rc::new(model.clone()),
+            DrivingModel::IntelligentDriver(model) => Rc::new(model.clone()),
+            DrivingModel::Custom(CustomDrivingModel { name, parameters }) => {
+                let constructor = self
+                    .constructors
+                    .get(name)
+                    .ok_or_else(|| CarFollowingError::Unregistered(name.clone()))?;
+                Rc::from(constructor(parameters))
+            }
+        })
+    }
+
+    /// Build the models of all profiles of cars
+    pub fn build(&self, cars: &[stateless::Car]) -> Result<CarFollowingModels, CarFollowingError> {
+        let models = cars
+            .iter()
+            .map(|car| self.model(&car.driving_model))
+            .collect::<Result<_, _>>()?;
+        Ok(CarFollowingModels { models })
+    }
+}
+
+/// Car following models of profiles of cars, built once by a registry
+#[derive(Clone, Default)]
+pub struct CarFollowingModels {
+    models: Vec<Rc<dyn CarFollowingModel>>,
+}
+
+impl fmt::Debug for CarFollowingModels {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "CarFollowingModels({})", self.models.len())
+    }
+}
+
+impl CarFollowingModels {
+    pub fn acceleration(&self, profile: usize, situation: &FollowingSituation) -> f64 {
+        self.models[profile].acceleration(situation)
+    }
+}
+
+impl CarFollowingModel for NormalDrivingModel {
+    fn acceleration(
+        &self,
+        &FollowingSituation {
+            velocity,
+            gap,
+            leader_velocity,
+            speed_limit,
+            ..
+        }: &FollowingSituation,
+    ) -> f64 {
+        let aim_cushion = self.min_cushion + self.cushion_velocity_factor * leader_velocity;
+        let dx = gap - aim_cushion; // if dx is greater than 0, the car should go faster than front_velocity
+        let aim_average_velocity = dx / self.prediction_time;
+        let following =
+            (aim_average_velocity + leader_velocity - velocity) * 2.0 / self.prediction_time;
+        // reach the speed limit in the prediction time
+        let limited = (speed_limit - velocity) / self.prediction_time;
+        following.min(limited)
+    }
+}
+
+impl CarFollowingModel for IntelligentDriverModel {
+    fn acceleration(
+        &self,
+        &FollowingSituation {
+            velocity,
+            gap,
+            leader_velocity,
+            speed_limit,
+            ..
+        }: &FollowingSituation,
+    ) -> f64 {
+        let desired_velocity = self.desired_velocity.min(speed_limit);
+        let approaching_rate = velocity - leader_velocity;
+        let desired_gap = self.min_gap
+            + (velocity * self.time_headway
+                + velocity * approaching_rate
+                    / (2.0 * (self.max_acceleration * self.comfortable_deceleration).sqrt()))
+            .max(0.0);
+        // avoid dividing by zero when the car touches the front object
+        let gap = gap.max(f64::EPSILON);
+        let interaction = (desired_gap / gap).powi(2);
+        // only brake where the car must not move
+        if desired_velocity <= 0.0 {
+            return -self.max_acceleration * interaction;
+        }
+        self.max_acceleration
+            * (1.0 - (velocity / desired_velocity).powf(self.acceleration_exponent) - interaction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::generate::stateless::{
+        generate_stateless_model, StatelessModelGenerationSettings,
+    };
+    use structopt::StructOpt;
+
+    fn idm() -> IntelligentDriverModel {
+        IntelligentDriverModel {
+            desired_velocity: 30.0,
+            time_headway: 1.5,
+            min_gap: 2.0,
+            max_acceleration: 1.0,
+            comfortable_deceleration: 1.5,
+            acceleration_exponent: 4.0,
+        }
+    }
+
+    fn situation(velocity: f64, gap: f64, leader_velocity: f64) -> FollowingSituation {
+        FollowingSituation {
+            velocity,
+            acceleration: 0.0,
+            gap,
+            leader_velocity,
+            speed_limit: f64::INFINITY,
+        }
+    }
+
+    #[test]
+    fn idm_free_road() {
+        let model = idm();
+        let start = model.acceleration(&situation(0.0, f64::INFINITY, 0.0));
+        assert!((start - model.max_acceleration).abs() < 1e-9);
+        let cruise = model.acceleration(&situation(30.0, f64::INFINITY, 30.0));
+        assert!(cruise.abs() < 1e-9);
+    }
+
+    #[test]
+    fn idm_equilibrium_gap() {
+        let model = idm();
+        let velocity = 20.0;
+        let equilibrium_gap = (model.min_gap + velocity * model.time_headway)
+            / (1.0 - (velocity / model.desired_velocity).powf(model.acceleration_exponent)).sqrt();
+        let acceleration = model.acceleration(&situation(velocity, equilibrium_gap, velocity));
+        assert!(acceleration.abs() < 1e-9);
+        assert!(model.acceleration(&situation(velocity, equilibrium_gap / 2.0, velocity)) < 0.0);
+    }
+
+    #[test]
+    fn idm_speed_limit() {
+        let model = idm();
+        let limited = FollowingSituation {
+            speed_limit: 20.0,
+            ..situation(20.0, f64::INFINITY, 20.0)
+        };
+        assert!(model.acceleration(&limited).abs() < 1e-9);
+        let over_limited = FollowingSituation {
+            velocity: 25.0,
+            ..limited
+        };
+        assert!(model.acceleration(&over_limited) < 0.0);
+        let closed = FollowingSituation {
+            speed_limit: 0.0,
+            ..situation(0.0, 10.0, 0.0)
+        };
+        let acceleration = model.acceleration(&closed);
+        assert!(acceleration.is_finite() && acceleration < 0.0);
+    }
+
+    #[test]
+    fn normal_speed_limit() {
+        let model = NormalDrivingModel {
+            prediction_time: 1.0,
+            min_cushion: 2.0,
+            cushion_velocity_factor: 0.5,
+        };
+        let limited = FollowingSituation {
+            speed_limit: 20.0,
+            ..situation(20.0, f64::INFINITY, 20.0)
+        };
+        assert!(model.acceleration(&limited).abs() < 1e-9);
+        let over_limited = FollowingSituation {
+            velocity: 25.0,
+            ..limited
+        };
+        assert!(model.acceleration(&over_limited) < 0.0);
+    }
+
+    #[test]
+    fn custom_model_round_trip() {
+        struct Constant(f64);
+        impl CarFollowingModel for Constant {
+            fn acceleration(&self, _situation: &FollowingSituation) -> f64 {
+                self.0
+            }
+        }
+        let mut registry = CarFollowingRegistry::new();
+        registry.register("constant", |parameters| Box::new(Constant(parameters[0])));
+        let model = DrivingModel::Custom(CustomDrivingModel {
+            name: "constant".to_string(),
+            parameters: vec![4.0],
+        });
+        let serialized = bincode::serialize(&model).unwrap();
+        let deserialized: DrivingModel = bincode::deserialize(&serialized).unwrap();
+        let mut cars = generate_stateless_model(StatelessModelGenerationSettings::from_iter(&[
+            "test", "--seed", "2",
+        ]))
+        .unwrap()
+        .cars;
+        let profile = cars.len() - 1;
+        cars[profile].driving_model = deserialized;
+        let models = registry.build(&cars).unwrap();
+        let acceleration = models.acceleration(profile, &situation(0.0, 1.0, 0.0));
+        assert!((acceleration - 4.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn unregistered_custom_model() {
+        let model = DrivingModel::Custom(CustomDrivingModel {
+            name: "unknown".to_string(),
+            parameters: vec![],
+        });
+        let error = CarFollowingRegistry::new().model(&model).err().unwrap();
+        assert!(matches!(&error, CarFollowingError::Unregistered(name) if name == "unknown"));
+        assert!(error.to_string().contains("is not registered"));
+    }
+}
@@ -0,0 +1,403 @@
+//! Nagel-Schreckenberg cellular automaton
+//!
+//! Each lane is discretized into cells of `cell_length`, a car occupies one
+//! cell and its velocity is measured in cells per step. In every step, each car
+//! on a lane
+//!
+//! 0. changes to a neighbour lane toward a lane allowing its turn, if the
+//!    target cell is free, the leader on the target lane is more than `v`
+//!    cells ahead and the follower more than `v_max` cells behind;
+//! 1. accelerates by one cell per step up to its max velocity;
+//! 2. slows down to the number of empty cells in front of it (including the
+//!    stop line of the intersection if it is not allowed to pass);
+//! 3. slows down by one cell per step with `dawdle_probability`;
+//! 4. moves forward by its velocity.
+//!
+//! All cars are updated from the same snapshot, so the lane change is a
+//! sub-step of its own: cars change to lower lanes in even steps and to higher
+//! lanes in odd steps, so that cars from both sides never move into the same
+//! cell, and a car which changed lane then moves behind the leaders of both
+//! lanes.
+//!
+//! Cars in intersections are updated by the continuous model with the step
+//! time as time delta.
+
+use crate::{
+    controller::{process_local_state::ProcessLocalState, UpdateController},
+    model::{
+        common::CarIndex,
+        stateful::{self, car::Location},
+        stateless,
+    },
+    util::rng::{self, Stream},
+};
+use piston_window::UpdateArgs;
+use rand::Rng;
+use structopt::StructOpt;
+
+#[derive(StructOpt, Clone, Debug)]
+pub struct CellularAutomatonSettings {
+    #[structopt(
+        name = "cellular-automaton-cell-length",
+        long = "cellular-automaton-cell-length",
+        default_value = "7.5"
+    )]
+    pub cell_length: f64,
+    #[structopt(
+        name = "cellular-automaton-step-time",
+        long = "cellular-automaton-step-time",
+        default_value = "1.0"
+    )]
+    pub step_time: f64,
+    #[structopt(
+        name = "cellular-automaton-dawdle-probability",
+        long = "cellular-automaton-dawdle-probability",
+        default_value = "0.3"
+    )]
+    pub dawdle_probability: f64,
+}
+
+impl UpdateController {
+    /// Update a car by one step of the cellular automaton
+    pub(super) fn update_car_cellular(
+        &self,
+        car_index: CarIndex,
+        local_state: &ProcessLocalState,
+        stateful: &stateful::Model,
+        stateless: &stateless::Model,
+    ) -> Option<stateful::Car> {
+        let CellularAutomatonSettings {
+            cell_length,
+            step_time,
+            dawdle_probability,
+        } = self.settings.cellular_automaton_settings;
+        let args = UpdateArgs { dt: step_time };
+        let car = &stateful.cars[car_index];
+        let rerouted = self.reroute(car_index, car, stateful, stateless);
+        let car = rerouted.as_ref().unwrap_or(car);
+        let (road_direction, road_index, lane_direction, lane_index, about_to_turn, position) =
+            match car.location {
+                Location::OnLane {
+                    road_direction,
+                    road_index,
+                    lane_direction,
+                    lane_index,
+                    about_to_turn,
+                    position,
+                } => (
+                    road_direction,
+                    road_index,
+                    lane_direction,
+                    lane_index,
+                    about_to_turn,
+                    position,
+                ),
+                _ => return self.update_car(car_index, local_state, stateful, stateless, args),
+            };
+        let stateless_car = stateless.car(car);
+        let mut rng = rng::stream_rng(
+            stateless.seed,
+            Stream::CarUpdate,
+            car_index as u64,
+            stateful.step,
+        );
+        let cell_of = |position: f64| (position / cell_length).floor() as usize;
+        let cells_per_step = |velocity: f64| (velocity * step_time / cell_length).floor() as usize;
+        let lanes = stateless.city.board.get_roads(road_direction)[road_index]
+            .as_ref()
+            .unwrap()
+            .lanes_to_direction(lane_direction);
+        let lane_cells = cell_of(stateless.city.road_length(road_direction, road_index));
+        let cell = cell_of(position);
+        let velocity = (car.velocity * step_time / cell_length).round() as usize;
+
+        // 0. lane change to reach a lane allowing the turn
+        let turn_rule = about_to_turn.to_turn_rule();
+        let from_lane_index = lane_index;
+        let lane_index = if lanes[lane_index].direction_rule.contains(turn_rule) {
+            lane_index
+        } else {
+            let target = lanes
+                .iter()
+                .enumerate()
+                .filter(|(_, lane)| lane.direction_rule.contains(turn_rule))
+                .map(|(index, _)| index)
+                .min_by_key(|index| (*index as isize - lane_index as isize).abs());
+            match target {
+                Some(target) if (target > lane_index) == (stateful.step % 2 == 1) => {
+                    let to_lane_index = if target > lane_index {
+                        lane_index + 1
+                    } else {
+                        lane_index - 1
+                    };
+                    let (follower, leader) = local_state
+                        .lane(road_direction, road_index, lane_direction, to_lane_index)
+                        .neighbours(position);
+                    // the leader is at or in front of the target cell
+                    let leader_gap = match leader {
+                        Some((leader_position, _)) => cell_of(leader_position) > cell + velocity,
+                        None => true,
+                    };
+                    let follower_max_velocity = cells_per_step(lanes[to_lane_index].max_speed);
+                    let follower_gap = match follower {
+                        Some((follower_position, _)) => {
+                            cell_of(follower_position) + follower_max_velocity < cell
+                        }
+                        None => true,
+                    };
+                    if leader_gap && follower_gap {
+                        to_lane_index
+                    } else {
+                        lane_index
+                    }
+                }
+                _ => lane_index,
+            }
+        };
+
+        // 1. acceleration
+        let speed_limit = lanes[lane_index].max_speed;
+        let max_velocity = cells_per_step(stateless_car.max_velocity.min(speed_limit));
+        let mut velocity = (velocity + 1).min(max_velocity);
+        // 2. slowing down
+        let mut leader = local_state
+            .lane(road_direction, road_index, lane_direction, lane_index)
+            .leader_of(car_index, position);
+        if lane_index != from_lane_index {
+            let (_, old_leader) = local_state
+                .lane(road_direction, road_index, lane_direction, from_lane_index)
+                .neighbours_of(car_index);
+            leader = match (leader, old_leader) {
+                (Some(new), Some(old)) if old.0 < new.0 => Some(old),
+                (new, old) => new.or(old),
+            };
+        }
+        if let Some((leader_position, _)) = leader {
+            velocity = velocity.min(cell_of(leader_position).saturating_sub(cell + 1));
+        } else if let Some((distance, _)) = local_state.path_leader(
+            stateless,
+            &stateful.cars,
+            (road_direction, road_index, lane_direction),
+            lane_index,
+            about_to_turn,
+        ) {
+            // look ahead through the intersection
+            let road_length = stateless.city.road_length(road_direction, road_index);
+            velocity = velocity.min(cell_of(road_length + distance).saturating_sub(cell + 1));
+        }
+        let stop_line_velocity = self.stop_line_velocity(
+            car_index,
+            local_state,
+            stateful,
+            stateless,
+            (road_direction, road_index, lane_direction),
+            about_to_turn,
+            speed_limit,
+        );
+        let arriving = car.trip.route.is_empty();
+        if stop_line_velocity <= 0.0
+            || (!arriving && !lanes[lane_index].direction_rule.contains(turn_rule))
+        {
+            // stop at the last cell of the lane
+            velocity = velocity.min(lane_cells.saturating_sub(cell + 1));
+        }
+        // 3. randomization
+        if velocity > 0 && rng.gen::<f64>() < dawdle_probability {
+            velocity -= 1;
+        }
+        // 4. car motion
+        let cell = cell + velocity;
+        let velocity = velocity as f64 * cell_length / step_time;
+        if cell >= lane_cells {
+            self.enter_intersection(
+                velocity,
+                car,
+                local_state,
+                stateful,
+                stateless,
+                (road_direction, road_index, lane_direction),
+                lane_index,
+            )
+        } else {
+            Some(stateful::Car {
+                location: Location::OnLane {
+                    road_direction,
+                    road_index,
+                    lane_direction,
+                    lane_index,
+                    about_to_turn,
+                    position: cell as f64 * cell_length,
+                },
+                velocity,
+                acceleration: 0.0,
+                trip: car.trip.clone(),
+                profile: car.profile,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        controller::{decomposition::heading_intersection, UpdateSettings},
+        model::{
+            board::RoadIndex,
+            common::{AxisDirection, LaneDirection, LaneIndex, RelativeDirection, TurnRule},
+            generate::{self, ModelGenerationSettings},
+            stateful::car::Trip,
+        },
+        util::pool::Pool,
+    };
+    use std::collections::VecDeque;
+
+    const CELL: f64 = 7.5;
+
+    /// A model with a road of two lanes to `LowToHigh` at least, and an
+    /// update controller of the cellular automaton without dawdling
+    fn setup() -> (
+        UpdateController,
+        stateless::Model,
+        stateful::Model,
+        (AxisDirection, RoadIndex),
+    ) {
+        let model =
+            generate::generate_model(ModelGenerationSettings::from_iter(&["test", "--seed", "1"]))
+                .unwrap();
+        let settings = UpdateSettings::from_iter(&[
+            "test",
+            "--update-engine",
+            "cellular-automaton",
+            "--cellular-automaton-dawdle-probability",
+            "0",
+            "--reroute-interval",
+            "0",
+        ]);
+        let city = &model.stateless.city;
+        let road = city
+            .board
+            .enumerate_roads()
+            .find(|(index, (direction, road))| {
+                matches!(road, Some(road) if road.lane_to_high.len() >= 2)
+                    && city.road_length(*direction, *index) > 10.0 * CELL
+            })
+            .map(|(index, (direction, _))| (direction, index))
+            .expect("no road with two lanes");
+        (
+            UpdateController::new(
+                settings,
+                &model.stateless,
+                &model.stateful,
+                Default::default(),
+            )
+            .unwrap(),
+            model.stateless,
+            model.stateful,
+            road,
+        )
+    }
+
+    fn car(
+        stateless: &stateless::Model,
+        (road_direction, road_index): (AxisDirection, RoadIndex),
+        lane_index: LaneIndex,
+        cell: usize,
+        velocity: f64,
+    ) -> stateful::Car {
+        let location = Location::OnLane {
+            road_direction,
+            road_index,
+            lane_direction: LaneDirection::LowToHigh,
+            lane_index,
+            about_to_turn: RelativeDirection::Left,
+            position: cell as f64 * CELL,
+        };
+        let destination = heading_intersection(&stateless.city.board, &location);
+        stateful::Car {
+            location,
+            velocity,
+            acceleration: 0.0,
+            trip: Trip {
+                origin: destination,
+                destination,
+                route: VecDeque::new(),
+            },
+            profile: 0,
+        }
+    }
+
+    fn update(
+        controller: &UpdateController,
+        stateless: &stateless::Model,
+        stateful: &stateful::Model,
+        car_index: CarIndex,
+    ) -> (LaneIndex, usize) {
+        let local_state =
+            ProcessLocalState::generate(&stateless.city, &stateful.cars, &stateless.cars);
+        let car = controller
+            .update_car_cellular(car_index, &local_state, stateful, stateless)
+            .unwrap();
+        match car.location {
+            Location::OnLane {
+                lane_index,
+                position,
+                ..
+            } => (lane_index, (position / CELL).round() as usize),
+            _ => panic!("car left the lane"),
+        }
+    }
+
+    #[test]
+    fn follower_stops_behind_leader() {
+        let (controller, stateless, mut stateful, road) = setup();
+        let fast = 30.0;
+        stateful.cars = Pool::from_entries(vec![(0, car(&stateless, road, 0, 0, fast))], 1);
+        let (_, alone) = update(&controller, &stateless, &stateful, 0);
+        assert!(alone > 0);
+        let leader = car(&stateless, road, 0, 1, 0.0);
+        stateful.cars =
+            Pool::from_entries(vec![(0, car(&stateless, road, 0, 0, fast)), (1, leader)], 2);
+        assert_eq!(update(&controller, &stateless, &stateful, 0), (0, 0));
+    }
+
+    #[test]
+    fn lane_change_needs_gap() {
+        let (controller, mut stateless, mut stateful, (direction, index)) = setup();
+        {
+            let road = stateless
+                .city
+                .board
+                .get_road_mut(direction, index)
+                .unwrap()
+                .as_mut()
+                .unwrap();
+            road.lane_to_high[0].direction_rule = TurnRule::FRONT;
+            road.lane_to_high[1].direction_rule = TurnRule::ALL;
+            // the follower moves two cells per step at most
+            road.lane_to_high[1].max_speed = 2.0 * CELL;
+        }
+        let road = (direction, index);
+        let changing = car(&stateless, road, 0, 5, 0.0);
+        // changes to higher lanes in odd steps only
+        stateful.step = 1;
+        stateful.cars = Pool::from_entries(vec![(0, changing.clone())], 1);
+        assert_eq!(update(&controller, &stateless, &stateful, 0).0, 1);
+        stateful.step = 2;
+        assert_eq!(update(&controller, &stateless, &stateful, 0).0, 0);
+
+        stateful.step = 1;
+        for (follower_cell, changed_lane) in vec![(3, 0), (2, 1)] {
+            let follower = car(&stateless, road, 1, follower_cell, 0.0);
+            stateful.cars = Pool::from_entries(vec![(0, changing.clone()), (1, follower)], 2);
+            assert_eq!(
+                update(&controller, &stateless, &stateful, 0).0,
+                changed_lane
+            );
+        }
+        // an occupied target cell
+        let blocking = car(&stateless, road, 1, 5, 0.0);
+        stateful.cars = Pool::from_entries(vec![(0, changing), (1, blocking)], 2);
+        assert_eq!(update(&controller, &stateless, &stateful, 0).0, 0);
+    }
+}
@@ -0,0 +1,106 @@
+//! Checkpoints of a running simulation.
+//!
+//! Random decisions draw from streams derived from the seed and the step of
+//! the model, so the model and a few fields of the update controller are
+//! enough to resume a simulation, on any number of processes.
+
+use crate::{controller::routing::RoadVelocities, model::Model};
+use quick_error::quick_error;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+use structopt::StructOpt;
+
+quick_error! {
+    #[derive(Debug)]
+    pub enum CheckpointError {
+        Io(err: io::Error) {
+            from()
+            display("IO error: {}", err)
+        }
+        Bincode(err: bincode::Error) {
+            from()
+            display("Bincode error: {}", err)
+        }
+    }
+}
+
+#[derive(StructOpt, Clone, Debug)]
+pub struct CheckpointSettings {
+    /// Updates between checkpoints, 0 to write checkpoints on SIGUSR1 only
+    #[structopt(
+        name = "checkpoint-interval",
+        long = "checkpoint-interval",
+        default_value = "0"
+    )]
+    pub interval: u64,
+    #[structopt(
+        name = "checkpoint-file",
+        long = "checkpoint-file",
+        default_value = "mpi-traffic.checkpoint",
+        parse(from_os_str)
+    )]
+    pub file: PathBuf,
+}
+
+/// State of the update controller kept in a checkpoint
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ControllerState {
+    pub cellular_automaton_elapsed: f64,
+    pub road_velocities: Option<RoadVelocities>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct Checkpoint {
+    pub model: Model,
+    pub controller: ControllerState,
+}
+
+impl Checkpoint {
+    /// Write the checkpoint to a temporary file first, so an interrupted
+    /// write keeps the previous checkpoint.
+    pub fn save(&self, path: &Path) -> Result<(), CheckpointError> {
+        let mut temporary = path.as_os_str().to_owned();
+        temporary.push(".tmp");
+        let temporary = PathBuf::from(temporary);
+        {
+            let mut writer = BufWriter::new(File::create(&temporary)?);
+            bincode::serialize_into(&mut writer, self)?;
+            // errors of flushing are lost when the writer is dropped
+            writer.flush()?;
+            writer.get_ref().sync_all()?;
+        }
+        fs::rename(&temporary, path)?;
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, CheckpointError> {
+        let reader = BufReader::new(File::open(path)?);
+        Ok(bincode::deserialize_from(reader)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn save_and_load() {
+        let path = std::env::temp_dir().join(format!(
+            "mpi-traffic-save-and-load-{}.checkpoint",
+            std::process::id()
+        ));
+        let mut checkpoint = Checkpoint::default();
+        checkpoint.model.stateful.step = 42;
+        checkpoint.controller.cellular_automaton_elapsed = 0.5;
+        checkpoint.save(&path).unwrap();
+        let loaded = Checkpoint::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.model.stateful.step, 42);
+        assert_eq!(loaded.controller.cellular_automaton_elapsed, 0.5);
+    }
+}
@@ -0,0 +1,231 @@
+//! Simulation clock of the interactive viewer.
+//!
+//! The simulation always advances in fixed steps, the clock decides how many
+//! steps to run for the wall clock time elapsed between updates of the window.
+
+use std::{str::FromStr, time::Instant};
+use structopt::StructOpt;
+
+#[derive(StructOpt, Clone, Debug)]
+pub struct ClockSettings {
+    /// Simulated seconds of each step, positive
+    #[structopt(
+        name = "clock-step",
+        long = "clock-step",
+        default_value = "0.05",
+        parse(try_from_str = parse_positive)
+    )]
+    pub step: f64,
+    /// Simulated seconds per wall clock second, or "max" to run as fast as
+    /// possible
+    #[structopt(name = "clock-speed", long = "clock-speed", default_value = "1")]
+    pub speed: ClockSpeed,
+    /// Steps run at most in an update of the window, the simulation slows
+    /// down instead of catching up beyond it
+    #[structopt(
+        name = "clock-max-steps",
+        long = "clock-max-steps",
+        default_value = "10"
+    )]
+    pub max_steps: u64,
+    /// Factor of the speed changed by the keyboard, positive
+    #[structopt(
+        name = "clock-speed-factor",
+        long = "clock-speed-factor",
+        default_value = "2",
+        parse(try_from_str = parse_positive)
+    )]
+    pub speed_factor: f64,
+}
+
+fn parse_positive(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(x) if x > 0.0 => Ok(x),
+        _ => Err(format!("expected a positive number: {}", s)),
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ClockSpeed {
+    Scaled(f64),
+    /// Run `max_steps` steps in each update of the window
+    Unlimited,
+}
+
+impl FromStr for ClockSpeed {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "max" {
+            return Ok(ClockSpeed::Unlimited);
+        }
+        match s.parse::<f64>() {
+            Ok(speed) if speed > 0.0 => Ok(ClockSpeed::Scaled(speed)),
+            _ => Err(format!("invalid clock speed: {}", s)),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Clock {
+    pub settings: ClockSettings,
+    /// Simulated seconds not run yet
+    accumulated: f64,
+    last: Option<Instant>,
+    paused: bool,
+}
+
+impl Clock {
+    pub fn new(settings: ClockSettings) -> Self {
+        Self {
+            settings,
+            accumulated: 0.0,
+            last: None,
+            paused: false,
+        }
+    }
+
+    pub fn paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+    }
+
+    pub fn speed_up(&mut self) {
+        if let ClockSpeed::Scaled(speed) = &mut self.settings.speed {
+            *speed *= self.settings.speed_factor;
+        }
+    }
+
+    /// Slow down by the speed factor, or to the real time from "max"
+    pub fn slow_down(&mut self) {
+        self.settings.speed = match self.settings.speed {
+            ClockSpeed::Scaled(speed) => ClockSpeed::Scaled(speed / self.settings.speed_factor),
+            ClockSpeed::Unlimited => ClockSpeed::Scaled(1.0),
+        };
+    }
+
+    /// Drop the elapsed time not run yet
+    pub fn reset(&mut self) {
+        self.accumulated = 0.0;
+    }
+
+    pub fn step(&self) -> f64 {
+        self.settings.step
+    }
+
+    /// Return the number of steps to run for the wall clock time elapsed
+    /// since the last call, no steps while paused.
+    pub fn advance(&mut self) -> u64 {
+        let now = Instant::now();
+        let elapsed = self
+            .last
+            .map(|last| now.duration_since(last).as_secs_f64())
+            .unwrap_or(0.0);
+        self.last = Some(now);
+        if self.paused {
+            0
+        } else {
+            self.advance_by(elapsed)
+        }
+    }
+
+    fn advance_by(&mut self, elapsed: f64) -> u64 {
+        let max_steps = self.settings.max_steps;
+        let speed = match self.settings.speed {
+            ClockSpeed::Scaled(speed) => speed,
+            ClockSpeed::Unlimited => {
+                self.accumulated = 0.0;
+                return max_steps;
+            }
+        };
+        self.accumulated += elapsed * speed;
+        let step = self.settings.step;
+        let steps = (self.accumulated / step).floor() as u64;
+        if steps > max_steps {
+            self.accumulated = 0.0;
+            max_steps
+        } else {
+            self.accumulated -= steps as f64 * step;
+            steps
+        }
+    }
+
+    /// Proportion of the next step already elapsed, to draw states between
+    /// steps, the last state is drawn while paused
+    pub fn alpha(&self) -> f64 {
+        if self.paused {
+            1.0
+        } else {
+            (self.accumulated / self.settings.step).clamp(0.0, 1.0)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clock(speed: ClockSpeed) -> Clock {
+        Clock::new(ClockSettings {
+            step: 0.1,
+            speed,
+            max_steps: 10,
+            speed_factor: 2.0,
+        })
+    }
+
+    #[test]
+    fn fixed_steps() {
+        let mut clock = clock(ClockSpeed::Scaled(2.0));
+        assert_eq!(clock.advance_by(0.025), 0);
+        assert!((clock.alpha() - 0.5).abs() < 1e-9);
+        assert_eq!(clock.advance_by(0.1), 2);
+        assert!((clock.alpha() - 0.5).abs() < 1e-9);
+        // too slow to catch up
+        assert_eq!(clock.advance_by(10.0), 10);
+        assert!(clock.alpha().abs() < 1e-9);
+    }
+
+    #[test]
+    fn parse_speed() {
+        assert_eq!("max".parse(), Ok(ClockSpeed::Unlimited));
+        assert_eq!("0.5".parse(), Ok(ClockSpeed::Scaled(0.5)));
+        assert!("0".parse::<ClockSpeed>().is_err());
+        assert_eq!(clock(ClockSpeed::Unlimited).advance_by(0.0), 10);
+    }
+
+    #[test]
+    fn parse_positive_settings() {
+        let settings = |option: &str, x: &str| {
+            ClockSettings::from_iter_safe(&["test", &format!("{}={}", option, x)])
+        };
+        for option in &["--clock-step", "--clock-speed-factor"] {
+            assert!(settings(option, "1.5").is_ok());
+            assert!(settings(option, "0").is_err());
+            assert!(settings(option, "-2").is_err());
+            assert!(settings(option, "NaN").is_err());
+        }
+        let settings = settings("--clock-step", "0.5").unwrap();
+        assert_eq!(settings.step, 0.5);
+    }
+
+    #[test]
+    fn change_speed() {
+        let mut clock = clock(ClockSpeed::Scaled(1.0));
+        clock.speed_up();
+        assert_eq!(clock.settings.speed, ClockSpeed::Scaled(2.0));
+        clock.slow_down();
+        clock.slow_down();
+        assert_eq!(clock.settings.speed, ClockSpeed::Scaled(0.5));
+        clock.settings.speed = ClockSpeed::Unlimited;
+        clock.slow_down();
+        assert_eq!(clock.settings.speed, ClockSpeed::Scaled(1.0));
+    }
+}
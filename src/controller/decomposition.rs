@@ -0,0 +1,321 @@
+//! Spatial domain decomposition of the board
+//!
+//! Each process owns a rectangular block of intersections. A lane belongs to
+//! the intersection it leads to, so a car is owned by the process owning the
+//! intersection the car is heading to or is in. Bounds of blocks move with
+//! measured loads to keep processes evenly busy.
+
+use crate::{
+    model::{
+        board::{Board, IntersectionIndex},
+        common::CarIndex,
+        stateful::{self, car::Location, Car},
+    },
+    util::pool::Pool,
+};
+use mpi::topology::Rank;
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
+/// Items to send to each process, in the order of ranks
+pub type Sends<T> = Vec<Vec<T>>;
+
+pub type IndexedCar = (CarIndex, Car);
+
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct Decomposition {
+    /// Block row `k` has board rows `row_bounds[k]..row_bounds[k + 1]`
+    row_bounds: Vec<usize>,
+    /// Block column `k` has board columns `col_bounds[k]..col_bounds[k + 1]`
+    col_bounds: Vec<usize>,
+}
+
+impl Decomposition {
+    /// Split a board of `shape` into at most `size` blocks of even sizes.
+    ///
+    /// The grid of blocks uses as many processes as possible and prefers
+    /// square blocks to keep boundaries short. Processes beyond the grid own
+    /// nothing.
+    pub fn new((rows, cols): (usize, usize), size: Rank) -> Self {
+        let size = size as usize;
+        let mut best = (1, 1);
+        for block_rows in 1..=rows.min(size) {
+            let block_cols = (size / block_rows).min(cols);
+            let imbalance =
+                |(r, c): (usize, usize)| (rows as f64 / r as f64 - cols as f64 / c as f64).abs();
+            let (best_rows, best_cols) = best;
+            if block_rows * block_cols > best_rows * best_cols
+                || (block_rows * block_cols == best_rows * best_cols
+                    && imbalance((block_rows, block_cols)) < imbalance(best))
+            {
+                best = (block_rows, block_cols);
+            }
+        }
+        let (block_rows, block_cols) = best;
+        Decomposition {
+            row_bounds: balanced_bounds(&vec![1.0; rows], block_rows),
+            col_bounds: balanced_bounds(&vec![1.0; cols], block_cols),
+        }
+    }
+
+    /// Move bounds of blocks to even loads of block rows and block columns,
+    /// keeping the grid of blocks
+    pub fn rebalanced(&self, load: &Load) -> Self {
+        Decomposition {
+            row_bounds: balanced_bounds(&load.rows, self.block_rows()),
+            col_bounds: balanced_bounds(&load.cols, self.block_cols()),
+        }
+    }
+
+    fn block_rows(&self) -> usize {
+        self.row_bounds.len() - 1
+    }
+
+    fn block_cols(&self) -> usize {
+        self.col_bounds.len() - 1
+    }
+
+    /// Number of processes owning a block
+    pub fn block_number(&self) -> usize {
+        self.block_rows() * self.block_cols()
+    }
+
+    /// Rows and columns of the board owned by `rank`, empty if `rank` owns
+    /// nothing
+    pub fn block(&self, rank: Rank) -> (Range<usize>, Range<usize>) {
+        let rank = rank as usize;
+        if rank >= self.block_number() {
+            return (0..0, 0..0);
+        }
+        let (k, l) = (rank / self.block_cols(), rank % self.block_cols());
+        (
+            self.row_bounds[k]..self.row_bounds[k + 1],
+            self.col_bounds[l]..self.col_bounds[l + 1],
+        )
+    }
+
+    pub fn owner(&self, (i, j): IntersectionIndex) -> Rank {
+        // the first bound is 0, so the partition point is at least 1
+        let k = self.row_bounds.partition_point(|bound| *bound <= i) - 1;
+        let l = self.col_bounds.partition_point(|bound| *bound <= j) - 1;
+        let k = k.min(self.block_rows() - 1);
+        let l = l.min(self.block_cols() - 1);
+        (k * self.block_cols() + l) as Rank
+    }
+
+    /// Owner of a car at `location`
+    pub fn car_owner<I, R>(&self, board: &Board<I, R>, location: &Location) -> Rank {
+        self.owner(heading_intersection(board, location))
+    }
+
+    /// Owner of the intersection a car on a lane at `location` comes from,
+    /// `None` for a car in an intersection
+    pub fn lane_start_owner<I, R>(&self, board: &Board<I, R>, location: &Location) -> Option<Rank> {
+        match *location {
+            Location::OnLane {
+                road_direction,
+                road_index,
+                lane_direction,
+                ..
+            }
+            | Location::ChangingLane {
+                road_direction,
+                road_index,
+                lane_direction,
+                ..
+            } => Some(self.owner(board.lane_to_intersection_index(
+                road_direction,
+                road_index,
+                lane_direction.opposite(),
+            ))),
+            Location::InIntersection { .. } => None,
+        }
+    }
+
+    /// Cars of `rank` to send to each of `size` processes as their halo,
+    /// cars on lanes starting at intersections of other processes
+    pub fn halo<I, R>(
+        &self,
+        board: &Board<I, R>,
+        rank: Rank,
+        size: Rank,
+        owned: &Pool<Car>,
+    ) -> Sends<IndexedCar> {
+        let mut send = vec![Vec::new(); size as usize];
+        for (car_index, car) in owned.iter() {
+            match self.lane_start_owner(board, &car.location) {
+                Some(start_owner) if start_owner != rank => {
+                    send[start_owner as usize].push((car_index, car.clone()))
+                }
+                _ => (),
+            }
+        }
+        send
+    }
+
+    /// Split updated cars of `rank` into cars it keeps and cars to send to
+    /// each of `size` processes now owning them
+    pub fn migration<I, R>(
+        &self,
+        board: &Board<I, R>,
+        rank: Rank,
+        size: Rank,
+        updated: Vec<IndexedCar>,
+    ) -> (Vec<IndexedCar>, Sends<IndexedCar>) {
+        let mut kept = Vec::new();
+        let mut send = vec![Vec::new(); size as usize];
+        for (car_index, car) in updated {
+            let owner = self.car_owner(board, &car.location);
+            if owner == rank {
+                kept.push((car_index, car));
+            } else {
+                send[owner as usize].push((car_index, car));
+            }
+        }
+        (kept, send)
+    }
+
+    /// States of intersections owned by `rank` in `previous` to send to each
+    /// of `size` processes owning them in `self`
+    pub fn moved_intersections(
+        &self,
+        previous: &Decomposition,
+        rank: Rank,
+        size: Rank,
+        city: &stateful::City,
+    ) -> Sends<(IntersectionIndex, stateful::Intersection)> {
+        let mut send = vec![Vec::new(); size as usize];
+        for (index, intersection) in city.board.intersections.enumerate() {
+            let owner = self.owner(index);
+            if previous.owner(index) == rank && owner != rank {
+                if let Some(intersection) = intersection {
+                    send[owner as usize].push((index, intersection.clone()));
+                }
+            }
+        }
+        send
+    }
+}
+
+/// Update costs in seconds summed over rows and columns of the board
+#[derive(Clone, Debug, Default)]
+pub struct Load {
+    pub rows: Vec<f64>,
+    pub cols: Vec<f64>,
+    /// Number of steps measured
+    pub steps: u64,
+}
+
+impl Load {
+    pub fn new((rows, cols): (usize, usize)) -> Self {
+        Load {
+            rows: vec![0.0; rows],
+            cols: vec![0.0; cols],
+            steps: 0,
+        }
+    }
+
+    pub fn add(&mut self, (i, j): IntersectionIndex, cost: f64) {
+        self.rows[i] += cost;
+        self.cols[j] += cost;
+    }
+}
+
+/// Bounds splitting lines with `load` into `blocks` non-empty blocks of
+/// nearly even loads.
+///
+/// A small even load is added to every line, so lines without load are
+/// split evenly.
+fn balanced_bounds(load: &[f64], blocks: usize) -> Vec<usize> {
+    let n = load.len();
+    let total: f64 = load.iter().sum();
+    let base = if total > 0.0 {
+        total / n as f64 * 0.01
+    } else {
+        1.0
+    };
+    let mut prefix = Vec::with_capacity(n + 1);
+    prefix.push(0.0);
+    for l in load {
+        prefix.push(prefix.last().unwrap() + l + base);
+    }
+    let total = prefix[n];
+    let mut bounds = vec![0];
+    for k in 1..blocks {
+        let target = total * k as f64 / blocks as f64;
+        // the line closest to the target, leaving a line to each block
+        let bound = (1..n)
+            .min_by(|a, b| {
+                (prefix[*a] - target)
+                    .abs()
+                    .partial_cmp(&(prefix[*b] - target).abs())
+                    .unwrap()
+            })
+            .unwrap_or(0);
+        let previous = *bounds.last().unwrap();
+        bounds.push(bound.max(previous + 1).min(n - (blocks - k)));
+    }
+    bounds.push(n);
+    bounds
+}
+
+/// The intersection a car at `location` is heading to or is in
+pub fn heading_intersection<I, R>(board: &Board<I, R>, location: &Location) -> IntersectionIndex {
+    match *location {
+        Location::OnLane {
+            road_direction,
+            road_index,
+            lane_direction,
+            ..
+        }
+        | Location::ChangingLane {
+            road_direction,
+            road_index,
+            lane_direction,
+            ..
+        } => board.lane_to_intersection_index(road_direction, road_index, lane_direction),
+        Location::InIntersection {
+            intersection_index, ..
+        } => intersection_index,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn square_blocks() {
+        let decomposition = Decomposition::new((4, 6), 6);
+        assert_eq!(decomposition.block_number(), 6);
+        assert_eq!(decomposition.block(0), (0..2, 0..2));
+        assert_eq!(decomposition.block(5), (2..4, 4..6));
+        assert_eq!(decomposition.owner((3, 3)), 4);
+        assert_eq!(decomposition.owner((1, 5)), 2);
+    }
+
+    #[test]
+    fn rebalance() {
+        let decomposition = Decomposition::new((8, 2), 2);
+        assert_eq!(decomposition.block(0), (0..4, 0..2));
+        let mut load = Load::new((8, 2));
+        for i in 0..8 {
+            load.add((i, 0), 1.0);
+        }
+        load.add((7, 1), 12.0);
+        let decomposition = decomposition.rebalanced(&load);
+        assert_eq!(decomposition.block(0), (0..7, 0..2));
+        assert_eq!(decomposition.owner((7, 0)), 1);
+        // no load at all
+        let decomposition = decomposition.rebalanced(&Load::new((8, 2)));
+        assert_eq!(decomposition.block(1), (4..8, 0..2));
+    }
+
+    #[test]
+    fn more_processes_than_intersections() {
+        let decomposition = Decomposition::new((2, 1), 3);
+        assert_eq!(decomposition.block_number(), 2);
+        assert_eq!(decomposition.owner((1, 0)), 1);
+        assert_eq!(decomposition.block(2), (0..0, 0..0));
+    }
+}
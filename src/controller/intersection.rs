@@ -0,0 +1,276 @@
+//! Conflict resolution of cars in intersections
+//!
+//! A car in an intersection moves along a straight path from the in lane to
+//! the out lane. Cars from the same in lane follow each other. When the paths
+//! of two cars cross, the car arriving later at the conflict point yields, a
+//! car whose front already reached the conflict point never yields.
+
+use crate::{
+    controller::{process_local_state::ProcessLocalState, UpdateController},
+    model::{
+        board::RoadIndex,
+        common::{
+            AbsoluteDirection, AxisDirection, CarIndex, InOutDirection, LaneDirection,
+            RelativeDirection,
+        },
+        stateful::{self, car::Location},
+        stateless,
+    },
+};
+use std::cmp::Ordering;
+
+/// Time for a car to arrive at a conflict point, or 0 if it is already there.
+fn arrival_time(conflict: f64, position: f64, velocity: f64) -> f64 {
+    if position >= conflict {
+        0.0
+    } else {
+        // cars stopped near the conflict point still arrive earlier
+        (conflict - position) / velocity.max(1.0)
+    }
+}
+
+/// Whether a movement has priority over opposing left turns
+fn is_front_or_right(direction: RelativeDirection) -> bool {
+    matches!(
+        direction,
+        RelativeDirection::Front | RelativeDirection::Right
+    )
+}
+
+impl UpdateController {
+    /// Calculate the acceleration of a car in an intersection.
+    ///
+    /// Leaders from the same in lane and conflict points where the car should
+    /// yield are considered as front objects.
+    pub(super) fn intersection_acceleration(
+        &self,
+        car_index: CarIndex,
+        local_state: &ProcessLocalState,
+        stateful: &stateful::Model,
+        stateless: &stateless::Model,
+        speed_limit: f64,
+    ) -> f64 {
+        let car = &stateful.cars[car_index];
+        let stateless_car = stateless.car(&stateful.cars[car_index]);
+        let (
+            intersection_index,
+            from_direction,
+            from_lane_index,
+            to_direction,
+            to_lane_index,
+            total_length,
+            position,
+        ) = match car.location {
+            Location::InIntersection {
+                intersection_index,
+                from_direction,
+                from_lane_index,
+                to_direction,
+                to_lane_index,
+                total_length,
+                position,
+            } => (
+                intersection_index,
+                from_direction,
+                from_lane_index,
+                to_direction,
+                to_lane_index,
+                total_length,
+                position,
+            ),
+            _ => unreachable!("car is not in an intersection"),
+        };
+        let (start, end) = stateless
+            .city
+            .intersection_path(
+                intersection_index,
+                from_direction,
+                from_lane_index,
+                to_direction,
+                to_lane_index,
+            )
+            .unwrap();
+
+        // (distance, velocity) of front objects, free road by default
+        let mut front_objects = vec![(f64::INFINITY, speed_limit)];
+        for &other_index in local_state.intersection_cars(intersection_index) {
+            if other_index == car_index {
+                continue;
+            }
+            let other = &stateful.cars[other_index];
+            let other_stateless = stateless.car(&stateful.cars[other_index]);
+            if let Location::InIntersection {
+                from_direction: other_from_direction,
+                from_lane_index: other_from_lane_index,
+                to_direction: other_to_direction,
+                to_lane_index: other_to_lane_index,
+                total_length: other_total_length,
+                position: other_position,
+                ..
+            } = other.location
+            {
+                if other_from_direction == from_direction
+                    && other_from_lane_index == from_lane_index
+                {
+                    // cars from the same lane follow each other
+                    if other_position > position {
+                        front_objects.push((
+                            other_position - other_stateless.length - position,
+                            other.velocity,
+                        ));
+                    }
+                    continue;
+                }
+                let (other_start, other_end) = stateless
+                    .city
+                    .intersection_path(
+                        intersection_index,
+                        other_from_direction,
+                        other_from_lane_index,
+                        other_to_direction,
+                        other_to_lane_index,
+                    )
+                    .unwrap();
+                let (proportion, other_proportion) =
+                    match start.segment_intersection(end, other_start, other_end) {
+                        Some(proportions) => proportions,
+                        None => continue,
+                    };
+                // stop before the conflict point with a clearance of half the
+                // width of the other car
+                let conflict = proportion * total_length - other_stateless.width / 2.0;
+                let other_conflict =
+                    other_proportion * other_total_length - stateless_car.width / 2.0;
+                if other_position - other_stateless.length > other_conflict + stateless_car.width {
+                    // the other car has passed the conflict point
+                    continue;
+                }
+                let arrival = arrival_time(conflict, position, car.velocity);
+                let other_arrival = arrival_time(other_conflict, other_position, other.velocity);
+                let yield_to_other = match other_arrival.partial_cmp(&arrival) {
+                    Some(Ordering::Less) => true,
+                    Some(Ordering::Equal) => other_index < car_index,
+                    _ => false,
+                };
+                if yield_to_other {
+                    front_objects.push((conflict - position, 0.0));
+                }
+            }
+        }
+        // the last car on the out lane
+        let out_road_index = stateless
+            .city
+            .board
+            .context_of_intersection(intersection_index)
+            .get(to_direction)
+            .unwrap();
+        let out_lane = local_state.lane(
+            to_direction.axis_direction(),
+            out_road_index,
+            LaneDirection::absolute_in_out_to_lane(to_direction, InOutDirection::Out),
+            to_lane_index,
+        );
+        if let Some(&(out_position, out_index)) = out_lane.cars.first() {
+            front_objects.push((
+                total_length - position + out_position
+                    - stateless.car(&stateful.cars[out_index]).length,
+                stateful.cars[out_index].velocity,
+            ));
+        }
+        let acceleration = front_objects
+            .into_iter()
+            .map(|(object_distance, object_velocity)| {
+                self.driver_acceleration(car, object_distance, object_velocity, speed_limit)
+            })
+            .min_by(|a, b| a.partial_cmp(b).unwrap())
+            .unwrap();
+        acceleration
+            .min(stateless_car.max_acceleration)
+            .max(-stateless_car.max_break_acceleration)
+    }
+
+    /// Whether a car at the end of a lane accepts the gap to opposing traffic
+    /// for a permissive left turn.
+    ///
+    /// The gap is rejected if an opposing car going straight or turning right
+    /// is in the intersection, or arrives at the intersection within the
+    /// critical gap of the car.
+    pub(super) fn accept_gap(
+        &self,
+        car_index: CarIndex,
+        local_state: &ProcessLocalState,
+        stateful: &stateful::Model,
+        stateless: &stateless::Model,
+        (road_direction, road_index, lane_direction): (AxisDirection, RoadIndex, LaneDirection),
+    ) -> bool {
+        let critical_gap = stateless.car(&stateful.cars[car_index]).critical_gap;
+        let intersection_index = stateless.city.board.lane_to_intersection_index(
+            road_direction,
+            road_index,
+            lane_direction,
+        );
+        // opposing cars come from the direction the car is heading to
+        let opposing_direction = AbsoluteDirection::of_lane(road_direction, lane_direction);
+
+        let in_intersection_conflicted = local_state
+            .intersection_cars(intersection_index)
+            .iter()
+            .any(|&other_index| match stateful.cars[other_index].location {
+                Location::InIntersection {
+                    from_direction,
+                    to_direction,
+                    ..
+                } => {
+                    from_direction == opposing_direction
+                        && is_front_or_right(from_direction.turn_back().should_turn(to_direction))
+                }
+                _ => false,
+            });
+        if in_intersection_conflicted {
+            return false;
+        }
+
+        let context = stateless
+            .city
+            .board
+            .context_of_intersection(intersection_index);
+        let opposing_road_index = match context.get(opposing_direction) {
+            Some(road_index) => *road_index,
+            None => return true,
+        };
+        let opposing_road_direction = opposing_direction.axis_direction();
+        let opposing_lane_direction =
+            LaneDirection::absolute_in_out_to_lane(opposing_direction, InOutDirection::In);
+        let opposing_road_length = stateless
+            .city
+            .road_length(opposing_road_direction, opposing_road_index);
+        let lanes_number = stateless.city.board.get_roads(opposing_road_direction)
+            [opposing_road_index]
+            .as_ref()
+            .unwrap()
+            .lanes_to_direction(opposing_lane_direction)
+            .len();
+        (0..lanes_number).all(|lane_index| {
+            local_state
+                .lane(
+                    opposing_road_direction,
+                    opposing_road_index,
+                    opposing_lane_direction,
+                    lane_index,
+                )
+                .cars
+                .iter()
+                .all(|&(position, other_index)| {
+                    let other = &stateful.cars[other_index];
+                    let about_to_turn = match other.location {
+                        Location::OnLane { about_to_turn, .. }
+                        | Location::ChangingLane { about_to_turn, .. } => about_to_turn,
+                        Location::InIntersection { .. } => unreachable!(),
+                    };
+                    !is_front_or_right(about_to_turn)
+                        || arrival_time(opposing_road_length, position, other.velocity)
+                            >= critical_gap
+                })
+        })
+    }
+}
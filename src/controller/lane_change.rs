@@ -0,0 +1,170 @@
+//! Lane changing decision with MOBIL (Minimizing Overall Braking Induced by
+//! Lane changes) model
+
+use crate::{
+    controller::{
+        process_local_state::{LaneCar, ProcessLocalState},
+        UpdateController,
+    },
+    model::{
+        board::RoadIndex,
+        common::{AxisDirection, CarIndex, LaneDirection, LaneIndex, RelativeDirection},
+        stateful,
+        stateless::{self, car::LaneChangeModel},
+    },
+};
+
+impl UpdateController {
+    /// Choose a lane to change to.
+    ///
+    /// Adjacent lanes are evaluated with the incentive criterion
+    /// `a'_c - a_c + p * (a'_n - a_n + a'_o - a_o) > threshold ± bias` and the
+    /// safety criterion `a'_n >= -safe_break_acceleration`, where `c` is the
+    /// car, `n` and `o` are the new and old followers, and `'` marks
+    /// accelerations after the lane change. The bias encourages the car to
+    /// keep right (lanes with higher index).
+    ///
+    /// A car never leaves a lane allowing it to turn to `about_to_turn` for a
+    /// lane that does not. If the current lane does not allow the turn, the car
+    /// changes lane towards a lane allowing the turn whenever it is safe.
+    #[allow(clippy::too_many_arguments)]
+    pub(super) fn choose_lane_change(
+        &self,
+        car_index: CarIndex,
+        local_state: &ProcessLocalState,
+        stateful: &stateful::Model,
+        stateless: &stateless::Model,
+        (road_direction, road_index, lane_direction): (AxisDirection, RoadIndex, LaneDirection),
+        lane_index: LaneIndex,
+        about_to_turn: RelativeDirection,
+    ) -> Option<LaneIndex> {
+        let lanes = stateless.city.board.get_roads(road_direction)[road_index]
+            .as_ref()
+            .unwrap()
+            .lanes_to_direction(lane_direction);
+        let LaneChangeModel::Mobil {
+            politeness,
+            threshold,
+            bias,
+            safe_break_acceleration,
+        } = stateless.car(&stateful.cars[car_index]).lane_change_model;
+        let position = stateful.cars[car_index].location.lane_position().unwrap();
+        let this = (position, car_index);
+
+        let turn_rule = about_to_turn.to_turn_rule();
+        let allow_turn = |index: LaneIndex| lanes[index].direction_rule.contains(turn_rule);
+        let target_lane_index = lanes
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| allow_turn(*index))
+            .map(|(index, _)| index)
+            .min_by_key(|index| (*index as isize - lane_index as isize).abs());
+
+        let (old_follower, old_leader) = local_state
+            .lane(road_direction, road_index, lane_direction, lane_index)
+            .neighbours_of(car_index);
+        let acceleration = |car: LaneCar, leader: Option<LaneCar>, speed_limit: f64| {
+            self.following_acceleration(car, leader, speed_limit, stateful, stateless)
+        };
+        let current_speed_limit = lanes[lane_index].max_speed;
+        let current = acceleration(this, old_leader, current_speed_limit);
+        let (old_follower_current, old_follower_changed) = match old_follower {
+            Some(old_follower) => (
+                acceleration(old_follower, Some(this), current_speed_limit),
+                acceleration(old_follower, old_leader, current_speed_limit),
+            ),
+            None => (0.0, 0.0),
+        };
+
+        let candidates = [
+            lane_index.checked_sub(1),
+            Some(lane_index + 1).filter(|index| *index < lanes.len()),
+        ];
+        let mut best: Option<(f64, LaneIndex)> = None;
+        for to_lane_index in candidates.iter().flatten().copied() {
+            let mandatory = match target_lane_index {
+                Some(target) if !allow_turn(lane_index) => {
+                    (target < lane_index) == (to_lane_index < lane_index)
+                }
+                _ => false,
+            };
+            if !mandatory && !allow_turn(to_lane_index) {
+                continue;
+            }
+
+            let (new_follower, new_leader) = local_state
+                .lane(road_direction, road_index, lane_direction, to_lane_index)
+                .neighbours(position);
+            let overlapped_with_leader = match new_leader {
+                Some((p, leader_index)) => {
+                    p - stateless.car(&stateful.cars[leader_index]).length <= position
+                }
+                None => false,
+            };
+            let overlapped_with_follower = match new_follower {
+                Some((p, _)) => p >= position - stateless.car(&stateful.cars[car_index]).length,
+                None => false,
+            };
+            if overlapped_with_leader || overlapped_with_follower {
+                // overlapped with a car on the new lane
+                continue;
+            }
+            let new_speed_limit = lanes[to_lane_index].max_speed;
+            let changed = acceleration(this, new_leader, new_speed_limit);
+            let (new_follower_current, new_follower_changed) = match new_follower {
+                Some(new_follower) => (
+                    acceleration(new_follower, new_leader, new_speed_limit),
+                    acceleration(new_follower, Some(this), new_speed_limit),
+                ),
+                None => (0.0, 0.0),
+            };
+            if new_follower_changed < -safe_break_acceleration {
+                continue;
+            }
+
+            if mandatory {
+                return Some(to_lane_index);
+            }
+            let incentive = changed - current
+                + politeness
+                    * (new_follower_changed - new_follower_current + old_follower_changed
+                        - old_follower_current);
+            let bias = if to_lane_index > lane_index {
+                bias
+            } else {
+                -bias
+            };
+            let advantage = incentive + bias - threshold;
+            let better = match best {
+                Some((best_advantage, _)) => advantage > best_advantage,
+                None => true,
+            };
+            if advantage > 0.0 && better {
+                best = Some((advantage, to_lane_index));
+            }
+        }
+        best.map(|(_, to_lane_index)| to_lane_index)
+    }
+
+    /// Acceleration of a car behind `leader` on its lane, or on a free road without a leader
+    fn following_acceleration(
+        &self,
+        (position, car_index): LaneCar,
+        leader: Option<LaneCar>,
+        speed_limit: f64,
+        stateful: &stateful::Model,
+        stateless: &stateless::Model,
+    ) -> f64 {
+        let stateless_car = stateless.car(&stateful.cars[car_index]);
+        let car = &stateful.cars[car_index];
+        let (gap, leader_velocity) = match leader {
+            Some((leader_position, leader_index)) => (
+                leader_position - stateless.car(&stateful.cars[leader_index]).length - position,
+                stateful.cars[leader_index].velocity,
+            ),
+            None => (f64::INFINITY, speed_limit),
+        };
+        self.driver_acceleration(car, gap, leader_velocity, speed_limit)
+            .min(stateless_car.max_acceleration)
+    }
+}
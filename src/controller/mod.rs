@@ -1,5 +1,5 @@
 use crate::{
-    communication::{self, Division},
+    communication,
     info::Info,
     model::{
         board::RoadIndex,
@@ -8,17 +8,51 @@ use crate::{
             InOutDirection::{self, Out},
             LaneDirection, LaneIndex, RelativeDirection, TurnRule,
         },
-        stateful::{self, Car},
-        stateless::{self, car::DrivingModel},
+        stateful::{self, car::Trip, Car},
+        stateless, Model,
     },
+    util::{
+        pool::Pool,
+        rng::{self, Stream},
+    },
+    view::Interpolation,
+    Error,
+};
+use car_following::{CarFollowingModels, CarFollowingRegistry, FollowingSituation};
+use cellular_automaton::CellularAutomatonSettings;
+use checkpoint::{Checkpoint, CheckpointSettings, ControllerState};
+use clock::{Clock, ClockSettings};
+use decomposition::{heading_intersection, Decomposition, Load};
+use mpi::{
+    collective::{CommunicatorCollectives, Root, SystemOperation},
+    topology::Rank,
 };
-use mpi::{collective::CommunicatorCollectives, topology::Rank};
-use piston_window::{Button, ButtonArgs, ButtonState, Input, Motion, MouseButton, UpdateArgs};
+use piston_window::{Button, ButtonArgs, ButtonState, Input, Key, Motion, MouseButton, UpdateArgs};
 use process_local_state::ProcessLocalState;
-use rand::{self, Rng};
+use rand::Rng;
+use routing::RoadVelocities;
+use serde::{Deserialize, Serialize};
+use std::{
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
 use structopt::StructOpt;
+use sync::{CityDelta, StepTraffic};
 
+pub mod car_following;
+pub mod cellular_automaton;
+pub mod checkpoint;
+pub mod clock;
+pub mod decomposition;
+mod intersection;
+mod lane_change;
 pub mod process_local_state;
+pub mod routing;
+pub mod sync;
 
 #[derive(Clone, Debug)]
 pub struct Controller {
@@ -27,27 +61,51 @@ pub struct Controller {
     pub start_drag_location: Option<(f64, f64)>,
     pub settings: ControllerSettings,
     pub update_controller: UpdateController,
+    pub clock: Clock,
+    /// Cars before the last step, to draw cars between steps
+    previous_cars: Option<Pool<Car>>,
+    /// A single step requested by the keyboard while paused
+    step_requested: bool,
+    reset_requested: bool,
 }
 
 #[derive(StructOpt, Clone, Debug)]
 pub struct ControllerSettings {
     #[structopt(name = "zoom-step", long = "zoom-step", default_value = "0.1")]
     pub zoom_step: f64,
+    #[structopt(flatten)]
+    pub clock_settings: ClockSettings,
 }
 
 impl Controller {
     pub fn new(update_controller: UpdateController, settings: ControllerSettings) -> Self {
+        let clock = Clock::new(settings.clock_settings.clone());
         Self {
             mouse_left_button_down: false,
             mouse_left_button_down_location: None,
             start_drag_location: None,
             settings,
             update_controller,
+            clock,
+            previous_cars: None,
+            step_requested: false,
+            reset_requested: false,
         }
     }
+
+    /// Cars to draw between the last two steps
+    pub fn interpolation(&self) -> Option<Interpolation<'_>> {
+        self.previous_cars.as_ref().map(|previous| Interpolation {
+            previous,
+            alpha: self.clock.alpha(),
+        })
+    }
 }
 
 impl Controller {
+    /// Handle dragging and zooming by the mouse, and the clock by the
+    /// keyboard: space to pause or resume, period to run a single step,
+    /// equals and minus to speed up and slow down, R to reset.
     pub fn input(
         &mut self,
         info: &mut Info,
@@ -88,12 +146,30 @@ impl Controller {
             Input::Move(Motion::MouseScroll([_x, y])) => {
                 info.zoom += y * self.settings.zoom_step;
             }
+            Input::Button(ButtonArgs {
+                state: ButtonState::Press,
+                button: Button::Keyboard(key),
+                ..
+            }) => match key {
+                Key::Space => self.clock.toggle_pause(),
+                Key::Period => {
+                    self.clock.pause();
+                    self.step_requested = true;
+                }
+                Key::Equals => self.clock.speed_up(),
+                Key::Minus => self.clock.slow_down(),
+                Key::R => self.reset_requested = true,
+                _ => (),
+            },
             _ => (),
         }
     }
 }
 
 impl Controller {
+    /// Run the steps of the clock elapsed since the last update of the
+    /// window and requested commands in the root, sending each command to
+    /// other processes.
     pub fn update<Comm>(
         &mut self,
         root: Rank,
@@ -101,23 +177,228 @@ impl Controller {
         _info: &mut Info,
         stateful: &mut stateful::Model,
         stateless: &stateless::Model,
-        args: UpdateArgs,
     ) where
         Comm: CommunicatorCollectives + Clone,
     {
+        if std::mem::take(&mut self.reset_requested) {
+            self.send(
+                root,
+                communicator.clone(),
+                Command::Reset,
+                stateful,
+                stateless,
+            );
+            self.clock.reset();
+            self.previous_cars = None;
+        }
+        let mut steps = self.clock.advance();
+        if std::mem::take(&mut self.step_requested) {
+            steps += 1;
+        }
+        for step in 0..steps {
+            if step + 1 == steps {
+                self.previous_cars = Some(stateful.cars.clone());
+            }
+            let args = UpdateArgs {
+                dt: self.clock.step(),
+            };
+            let command = Command::Update(args);
+            self.send(root, communicator.clone(), command, stateful, stateless);
+        }
+    }
+
+    /// Broadcast a command from the root and execute it
+    fn send<Comm>(
+        &mut self,
+        root: Rank,
+        communicator: Comm,
+        mut command: Command,
+        stateful: &mut stateful::Model,
+        stateless: &stateless::Model,
+    ) where
+        Comm: CommunicatorCollectives + Clone,
+    {
+        let rank = communicator.rank();
+        let root_process = communicator.process_at_rank(root);
+        communication::bincode_broadcast(rank, root_process, &mut command).unwrap();
         self.update_controller
-            .update(root, communicator, stateful, stateless, args);
+            .execute(root, communicator, command, stateful, stateless);
     }
 }
 
-#[derive(Clone, Debug, Default)]
+/// Commands sent by the root to all processes, so they run in lockstep
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub enum Command {
+    Update(UpdateArgs),
+    /// Restart the simulation from its initial model
+    Reset,
+    /// Stop the simulation
+    Exit,
+}
+
+/// Settings of `UpdateController`, should be the same in all processes.
+#[derive(StructOpt, Clone, Debug)]
+pub struct UpdateSettings {
+    #[structopt(
+        name = "update-engine",
+        long = "update-engine",
+        default_value = "continuous",
+        possible_values = &["continuous", "cellular-automaton"]
+    )]
+    pub engine: UpdateEngine,
+    #[structopt(flatten)]
+    pub cellular_automaton_settings: CellularAutomatonSettings,
+    /// Steps between route re-planning of an informed driver, 0 to disable
+    #[structopt(
+        name = "reroute-interval",
+        long = "reroute-interval",
+        default_value = "50"
+    )]
+    pub reroute_interval: u64,
+    /// Steps between repartitions of blocks by measured update costs, 0 to
+    /// disable
+    #[structopt(
+        name = "rebalance-interval",
+        long = "rebalance-interval",
+        default_value = "100"
+    )]
+    pub rebalance_interval: u64,
+    #[structopt(flatten)]
+    pub checkpoint_settings: CheckpointSettings,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum UpdateEngine {
+    /// Continuous car following and lane changing models
+    Continuous,
+    /// Nagel-Schreckenberg cellular automaton
+    CellularAutomaton,
+}
+
+impl FromStr for UpdateEngine {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "continuous" => Ok(UpdateEngine::Continuous),
+            "cellular-automaton" => Ok(UpdateEngine::CellularAutomaton),
+            _ => Err(format!("unknown update engine: {}", s)),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct UpdateController {
-    car_out_rank: Rank,
+    /// Car following models of profiles of cars
+    car_following_models: CarFollowingModels,
+    settings: UpdateSettings,
+    /// Time elapsed since last step of the cellular automaton
+    cellular_automaton_elapsed: f64,
+    /// Blocks of the board owned by processes, created at the first update
+    decomposition: Option<Decomposition>,
+    /// Update costs of this process since the last repartition
+    load: Load,
+    /// Sources known by all processes, kept by the root to send changes
+    synced_sources: Option<Vec<stateful::Source>>,
+    /// Bytes exchanged since the last step of cars
+    traffic: StepTraffic,
+    /// Bytes exchanged in the last step of cars
+    last_traffic: StepTraffic,
+    /// Velocities of cars on roads of the whole city for rerouting, summed
+    /// every `reroute_interval` steps
+    road_velocities: Option<RoadVelocities>,
+    /// Updates since the last checkpoint
+    checkpoint_elapsed: u64,
+    /// Set by SIGUSR1, only read in the root
+    checkpoint_requested: Arc<AtomicBool>,
+    /// The model and the controller state the simulation started from
+    initial: (stateful::Model, ControllerState),
 }
 
 impl UpdateController {
-    pub fn new() -> Self {
-        Self { car_out_rank: 0 }
+    /// Create an update controller starting from `stateful` and the
+    /// controller state of a checkpoint, which are kept to reset the
+    /// simulation
+    pub fn new(
+        settings: UpdateSettings,
+        stateless: &stateless::Model,
+        stateful: &stateful::Model,
+        state: ControllerState,
+    ) -> Result<Self, Error> {
+        Self::with_car_following_registry(
+            settings,
+            &CarFollowingRegistry::new(),
+            stateless,
+            stateful,
+            state,
+        )
+    }
+
+    /// Create an update controller supporting custom car following models
+    /// registered in `car_following_registry`, return an error if a profile
+    /// of `stateless` refers to an unregistered model
+    pub fn with_car_following_registry(
+        settings: UpdateSettings,
+        car_following_registry: &CarFollowingRegistry,
+        stateless: &stateless::Model,
+        stateful: &stateful::Model,
+        state: ControllerState,
+    ) -> Result<Self, Error> {
+        Ok(Self {
+            car_following_models: car_following_registry.build(&stateless.cars)?,
+            settings,
+            cellular_automaton_elapsed: state.cellular_automaton_elapsed,
+            decomposition: None,
+            load: Load::default(),
+            synced_sources: None,
+            traffic: StepTraffic::default(),
+            last_traffic: StepTraffic::default(),
+            road_velocities: state.road_velocities.clone(),
+            checkpoint_elapsed: 0,
+            checkpoint_requested: Self::register_checkpoint_signal(),
+            initial: (stateful.clone(), state),
+        })
+    }
+
+    fn register_checkpoint_signal() -> Arc<AtomicBool> {
+        let requested = Arc::new(AtomicBool::new(false));
+        if let Err(e) = signal_hook::flag::register(signal_hook::consts::SIGUSR1, requested.clone())
+        {
+            log::warn!("failed to register checkpoint signal: {}", e);
+        }
+        requested
+    }
+
+    /// Restart the simulation from the model the controller was created with
+    pub fn reset(&mut self, stateful: &mut stateful::Model) {
+        let (initial, state) = self.initial.clone();
+        *stateful = initial;
+        self.cellular_automaton_elapsed = state.cellular_automaton_elapsed;
+        self.road_velocities = state.road_velocities;
+        self.decomposition = None;
+        self.load = Load::default();
+        self.synced_sources = None;
+        self.traffic = StepTraffic::default();
+        self.last_traffic = StepTraffic::default();
+        self.checkpoint_elapsed = 0;
+    }
+
+    /// Execute a command of the root, `Exit` is left to the caller
+    pub fn execute<Comm>(
+        &mut self,
+        root: Rank,
+        communicator: Comm,
+        command: Command,
+        stateful: &mut stateful::Model,
+        stateless: &stateless::Model,
+    ) where
+        Comm: CommunicatorCollectives + Clone,
+    {
+        match command {
+            Command::Update(args) => self.update(root, communicator, stateful, stateless, args),
+            Command::Reset => self.reset(stateful),
+            Command::Exit => (),
+        }
     }
 
     pub fn update<Comm>(
@@ -130,60 +411,346 @@ impl UpdateController {
     ) where
         Comm: CommunicatorCollectives + Clone,
     {
-        self.update_city(
-            root,
-            communicator.clone(),
-            &mut stateful.city,
-            &stateless.city,
-            args,
-        );
-        self.update_cars(root, communicator.clone(), stateful, stateless, args);
+        if self.decomposition.is_none() {
+            let shape = stateless.city.board.shape();
+            self.decomposition = Some(Decomposition::new(shape, communicator.size()));
+            self.load = Load::new(shape);
+        }
+        self.update_city(root, communicator.clone(), stateful, stateless, args);
+        match self.settings.engine {
+            UpdateEngine::Continuous => {
+                self.update_cars(root, communicator.clone(), stateful, stateless, args)
+            }
+            UpdateEngine::CellularAutomaton => {
+                let step_time = self.settings.cellular_automaton_settings.step_time;
+                self.cellular_automaton_elapsed += args.dt;
+                if self.cellular_automaton_elapsed >= step_time {
+                    self.cellular_automaton_elapsed -= step_time;
+                    self.update_cars(root, communicator.clone(), stateful, stateless, args);
+                }
+            }
+        }
+
+        // the root decides on checkpoints requested by signals
+        self.checkpoint_elapsed += 1;
+        let interval = self.settings.checkpoint_settings.interval;
+        let mut save = (interval > 0 && self.checkpoint_elapsed >= interval)
+            || (communicator.rank() == root
+                && self.checkpoint_requested.swap(false, Ordering::Relaxed));
+        communicator.process_at_rank(root).broadcast_into(&mut save);
+        if save {
+            self.checkpoint_elapsed = 0;
+            if let Some(checkpoint) = self.checkpoint(root, communicator, stateful, stateless) {
+                let path = &self.settings.checkpoint_settings.file;
+                match checkpoint.save(path) {
+                    Ok(()) => log::info!(
+                        "step {}: checkpoint written to {}",
+                        stateful.step,
+                        path.display()
+                    ),
+                    Err(e) => log::error!("failed to write checkpoint: {}", e),
+                }
+            }
+        }
+    }
+
+    /// Collect the state of the simulation in the root, return `None` in
+    /// other processes.
+    ///
+    /// The root knows all sources, but only approximations of cars and
+    /// signals of intersections owned by other processes, so owners send
+    /// their cars and intersections.
+    fn checkpoint<Comm>(
+        &self,
+        root: Rank,
+        communicator: Comm,
+        stateful: &stateful::Model,
+        stateless: &stateless::Model,
+    ) -> Option<Checkpoint>
+    where
+        Comm: CommunicatorCollectives,
+    {
+        let rank = communicator.rank();
+        let board = &stateless.city.board;
+        let decomposition = self.decomposition.as_ref().unwrap();
+        let intersections = stateful
+            .city
+            .board
+            .intersections
+            .enumerate()
+            .filter(|(index, _)| decomposition.owner(*index) == rank)
+            .filter_map(|(index, intersection)| {
+                intersection
+                    .as_ref()
+                    .map(|intersection| (index, intersection.clone()))
+            })
+            .collect::<Vec<_>>();
+        let cars = stateful
+            .cars
+            .iter()
+            .filter(|(_, car)| decomposition.car_owner(board, &car.location) == rank)
+            .map(|(car_index, car)| (car_index, car.clone()))
+            .collect::<Vec<_>>();
+        let (gathered, _) =
+            communication::bincode_gather_varcount(communicator, root, &(intersections, cars))
+                .unwrap();
+        gathered.map(|gathered| {
+            let mut stateful = stateful.clone();
+            let mut cars = Vec::new();
+            for (intersections, owned_cars) in gathered {
+                for (index, intersection) in intersections {
+                    stateful.city.board.intersections[index] = Some(intersection);
+                }
+                cars.extend(owned_cars);
+            }
+            stateful.cars = Pool::from_entries(cars, stateful.cars.next_id());
+            Checkpoint {
+                model: Model {
+                    stateless: stateless.clone(),
+                    stateful,
+                },
+                controller: ControllerState {
+                    cellular_automaton_elapsed: self.cellular_automaton_elapsed,
+                    road_velocities: self.road_velocities.clone(),
+                },
+            }
+        })
+    }
 
-        self.car_out_rank += 1;
-        self.car_out_rank %= communicator.size();
+    /// Bytes exchanged by this process in the last step of cars
+    pub fn last_traffic(&self) -> StepTraffic {
+        self.last_traffic
     }
 
+    /// Update cars of the block of this process.
+    ///
+    /// Cars on lanes leading out of the block are exchanged with neighbors
+    /// as the halo before the update, and cars leaving the block migrate to
+    /// their new owners after the update. The root collects changes of cars
+    /// of other processes for the view.
     pub fn update_cars<Comm>(
         &mut self,
-        _root: Rank,
+        root: Rank,
         communicator: Comm,
         stateful: &mut stateful::Model,
         stateless: &stateless::Model,
         args: UpdateArgs,
     ) where
-        Comm: CommunicatorCollectives,
+        Comm: CommunicatorCollectives + Clone,
     {
-        let local_state =
-            ProcessLocalState::generate(&stateless.city, &stateful.cars[..], &stateless.cars[..]);
-
-        let car_number = stateful.cars.len();
         let rank = communicator.rank();
         let size = communicator.size();
-        let division = Division::new(car_number, rank, size);
-        let mut local_cars = Vec::new();
-        let mut outed = false;
-        for car_index in division.range() {
-            local_cars.push(self.update_car(
-                &mut outed,
-                communicator.rank(),
-                car_index,
+        let board = &stateless.city.board;
+        let decomposition = self.decomposition.clone().unwrap();
+
+        // the root keeps cars of other processes for the view only
+        let mut view = Pool::new();
+        if rank == root {
+            view = stateful.cars.clone();
+            view.retain(|_, car| decomposition.car_owner(board, &car.location) != rank);
+        }
+        stateful
+            .cars
+            .retain(|_, car| decomposition.car_owner(board, &car.location) == rank);
+        let previous = stateful.cars.clone();
+        // velocities are summed at the start of each interval of rerouting
+        let sum_velocities = match stateful.step.checked_rem(self.settings.reroute_interval) {
+            Some(0) => true,
+            Some(_) => self.road_velocities.is_none(),
+            None => false,
+        };
+        if sum_velocities {
+            self.road_velocities = Some(Self::road_velocities(
+                communicator.clone(),
+                &stateful.cars,
+                &stateless.city,
+            ));
+        }
+        let halo_send = decomposition.halo(board, rank, size, &stateful.cars);
+        let (halo, halo_traffic) =
+            communication::bincode_all_to_all_varcount(communicator.clone(), &halo_send).unwrap();
+        self.traffic.halo = halo_traffic;
+        let owned = stateful.cars.ids().collect::<Vec<_>>();
+        let next_id = stateful.cars.next_id();
+        let mut known = std::mem::take(&mut stateful.cars).into_entries();
+        known.extend(halo);
+        stateful.cars = Pool::from_entries(known, next_id);
+        let local_state =
+            ProcessLocalState::generate(&stateless.city, &stateful.cars, &stateless.cars);
+
+        // at most one waiting car of each source is spawned in each step by the
+        // owner of the source, new cars take the next IDs. Cars arrive at
+        // sources in simulated time and wait until spawned, so the insertion
+        // rate does not depend on the update rate or the number of processes.
+        let spawns = (0..stateful.city.sources.len())
+            .filter(|source_index| stateful.city.sources[*source_index].waiting > 0)
+            .enumerate()
+            .map(|(k, source_index)| (next_id + k, source_index))
+            .collect::<Vec<_>>();
+        let mut updated = Vec::new();
+        for car_index in owned {
+            let start = Instant::now();
+            let car = match self.settings.engine {
+                UpdateEngine::Continuous => {
+                    self.update_car(car_index, &local_state, &*stateful, stateless, args)
+                }
+                UpdateEngine::CellularAutomaton => {
+                    self.update_car_cellular(car_index, &local_state, &*stateful, stateless)
+                }
+            };
+            let intersection = heading_intersection(board, &stateful.cars[car_index].location);
+            self.load.add(intersection, start.elapsed().as_secs_f64());
+            if let Some(car) = car {
+                updated.push((car_index, car));
+            }
+        }
+        for (car_index, source_index) in spawns.iter() {
+            let source = &stateless.city.sources[*source_index];
+            if decomposition.owner(source.intersection) != rank {
+                continue;
+            }
+            let start = Instant::now();
+            let car = self.spawn_car(
+                *car_index,
+                *source_index,
                 &local_state,
                 &*stateful,
                 stateless,
-                args,
-            ));
+            );
+            self.load
+                .add(source.intersection, start.elapsed().as_secs_f64());
+            if let Some(car) = car {
+                updated.push((*car_index, car));
+            }
+        }
+        self.load.steps += 1;
+        let interval = self.settings.rebalance_interval;
+        let decomposition = if interval > 0 && self.load.steps >= interval {
+            self.rebalance(communicator.clone(), &mut stateful.city);
+            self.decomposition.clone().unwrap()
+        } else {
+            decomposition
+        };
+
+        let (mut cars, migration_send) = decomposition.migration(board, rank, size, updated);
+        let (migrated, migration_traffic) =
+            communication::bincode_all_to_all_varcount(communicator.clone(), &migration_send)
+                .unwrap();
+        self.traffic.migration += migration_traffic;
+        cars.extend(migrated);
+        let next_id = next_id + spawns.len();
+        stateful.cars = Pool::from_entries(cars, next_id);
+
+        let deltas = if rank == root {
+            Vec::new()
+        } else {
+            sync::car_deltas(&previous, &stateful.cars)
+        };
+        let (gathered, view_traffic) =
+            communication::bincode_gather_varcount(communicator, root, &deltas).unwrap();
+        self.traffic.view = view_traffic;
+        if let Some(gathered) = gathered {
+            sync::apply_car_deltas(&mut view, gathered.into_iter().flatten().collect());
+            let own = std::mem::take(&mut stateful.cars);
+            let cars = own.into_entries().into_iter().chain(view.into_entries());
+            stateful.cars = Pool::from_entries(cars.collect(), next_id);
+            for (car_index, source_index) in spawns {
+                if stateful.cars.contains(car_index) {
+                    stateful.city.sources[source_index].waiting -= 1;
+                }
+            }
         }
-        let gathered =
-            communication::bincode_all_gather_varcount(communicator, &local_cars).unwrap();
-        stateful.cars = gathered.into_iter().flatten().collect();
+        log::info!(
+            "step {}: exchanged bytes {:?}, total {:?}",
+            stateful.step,
+            self.traffic,
+            self.traffic.total()
+        );
+        self.last_traffic = std::mem::take(&mut self.traffic);
+        stateful.step += 1;
     }
 
-    // TODO: fix this
-    #[allow(clippy::too_many_arguments)]
+    /// Sum velocities of cars owned by all processes over roads
+    fn road_velocities<Comm>(
+        communicator: Comm,
+        owned: &Pool<stateful::Car>,
+        city: &stateless::City,
+    ) -> RoadVelocities
+    where
+        Comm: CommunicatorCollectives,
+    {
+        use crate::model::stateful::car::Location::*;
+        let mut own = RoadVelocities::new(city);
+        for (_, car) in owned.iter() {
+            match car.location {
+                OnLane {
+                    road_direction,
+                    road_index,
+                    lane_direction,
+                    ..
+                }
+                | ChangingLane {
+                    road_direction,
+                    road_index,
+                    lane_direction,
+                    ..
+                } => own.add(
+                    city,
+                    (road_direction, road_index, lane_direction),
+                    car.velocity,
+                ),
+                InIntersection { .. } => (),
+            }
+        }
+        let mut all = RoadVelocities::new(city);
+        communicator.all_reduce_into(&own.sums[..], &mut all.sums[..], SystemOperation::sum());
+        communicator.all_reduce_into(&own.counts[..], &mut all.counts[..], SystemOperation::sum());
+        all
+    }
+
+    /// Repartition blocks by loads measured in all processes since the last
+    /// repartition.
+    ///
+    /// States of moved intersections are sent to their new owners, and cars of
+    /// moved blocks then migrate to their new owners with other leaving cars.
+    /// Updates of cars do not depend on the blocks, so measured costs change
+    /// the wall time of steps but not the trajectories.
+    fn rebalance<Comm>(&mut self, communicator: Comm, city: &mut stateful::City)
+    where
+        Comm: CommunicatorCollectives,
+    {
+        let rank = communicator.rank();
+        let shape = city.board.shape();
+        let mut load = Load::new(shape);
+        communicator.all_reduce_into(
+            &self.load.rows[..],
+            &mut load.rows[..],
+            SystemOperation::sum(),
+        );
+        communicator.all_reduce_into(
+            &self.load.cols[..],
+            &mut load.cols[..],
+            SystemOperation::sum(),
+        );
+        let previous = self.decomposition.as_ref().unwrap();
+        let decomposition = previous.rebalanced(&load);
+        log::info!("rebalanced blocks: {:?}", decomposition);
+        let send = decomposition.moved_intersections(previous, rank, communicator.size(), city);
+        let (received, traffic) =
+            communication::bincode_all_to_all_varcount(communicator, &send).unwrap();
+        self.traffic.migration += traffic;
+        for (index, intersection) in received {
+            city.board.intersections[index] = Some(intersection);
+        }
+        self.decomposition = Some(decomposition);
+        self.load = Load::new(shape);
+    }
+
+    /// Update a car, return `None` if the car leaves the city.
+    ///
+    /// Random decisions of the car draw from its own stream of the step.
     pub fn update_car(
         &self,
-        outed: &mut bool,
-        rank: Rank,
         car_index: CarIndex,
         local_state: &ProcessLocalState,
         stateful: &stateful::Model,
@@ -191,347 +758,603 @@ impl UpdateController {
         args: UpdateArgs,
     ) -> Option<stateful::Car> {
         use crate::model::stateful::car::Location::*;
-        let stateless_car = &stateless.cars[car_index];
-        if let Some(car) = &stateful.cars[car_index] {
-            match &car.location {
-                OnLane {
-                    road_direction,
-                    road_index,
-                    lane_direction,
-                    lane_index,
-                    about_to_turn,
-                    position,
-                } => {
-                    let road_length = stateless.city.road_length(*road_direction, *road_index);
-                    let road = stateless.city.board.get_roads(*road_direction)[*road_index]
-                        .as_ref()
-                        .unwrap();
-                    let lane = &road.lanes_to_direction(*lane_direction)[*lane_index];
-                    // let max_velocity = stateless_car.max_velocity.min(lane.max_speed);
-                    let max_velocity = stateless_car.max_velocity;
-                    let mut velocity =
-                        (car.velocity + car.acceleration * args.dt).min(max_velocity);
-                    if velocity < 0.0 {
-                        velocity = 0.0;
-                    }
-                    let position = position + car.velocity * args.dt;
-
-                    if position >= road_length {
-                        // switch to InIntersection
-                        let intersection_index = stateless.city.board.lane_to_intersection_index(
-                            *road_direction,
-                            *road_index,
-                            *lane_direction,
-                        );
-                        let driver_direction =
-                            AbsoluteDirection::of_lane(*road_direction, *lane_direction);
-                        let to_direction = driver_direction.turn(*about_to_turn);
-                        let from_direction = driver_direction.turn_back();
-                        let to_lane_index = {
-                            let mut rng = rand::thread_rng();
-                            let road_index = *stateless
-                                .city
-                                .board
-                                .context_of_intersection(intersection_index)
-                                .get(to_direction)
-                                .as_ref()
-                                .expect("no way to turn");
-                            let lane_direction = LaneDirection::absolute_in_out_to_lane(
-                                to_direction,
-                                InOutDirection::Out,
-                            );
-                            let road = stateless
-                                .city
-                                .board
-                                .get_road(to_direction.axis_direction(), road_index)
-                                .unwrap()
-                                .as_ref()
-                                .unwrap();
-                            let size = road.lanes_to_direction(lane_direction).len();
-                            rng.gen_range(0..size)
-                        };
-                        let total_length = stateless
-                            .city
-                            .intersection_path_total_length(
-                                intersection_index,
-                                from_direction,
-                                *lane_index,
-                                to_direction,
-                                to_lane_index,
-                            )
-                            .unwrap();
-                        let location = InIntersection {
-                            intersection_index,
-                            from_direction,
+        let car = &stateful.cars[car_index];
+        let stateless_car = stateless.car(car);
+        let mut rng = rng::stream_rng(
+            stateless.seed,
+            Stream::CarUpdate,
+            car_index as u64,
+            stateful.step,
+        );
+        let rerouted = self.reroute(car_index, car, stateful, stateless);
+        let car = rerouted.as_ref().unwrap_or(car);
+        match &car.location {
+            OnLane {
+                road_direction,
+                road_index,
+                lane_direction,
+                lane_index,
+                about_to_turn,
+                position,
+            } => {
+                let road_length = stateless.city.road_length(*road_direction, *road_index);
+                let speed_limit = stateless.city.lane_speed_limit(
+                    (*road_direction, *road_index, *lane_direction),
+                    &[*lane_index],
+                );
+                let max_velocity = stateless_car.max_velocity.min(speed_limit);
+                let velocity = Self::next_velocity(car, max_velocity, args.dt);
+                let position = position + car.velocity * args.dt;
+
+                if position >= road_length {
+                    self.enter_intersection(
+                        car.velocity,
+                        car,
+                        local_state,
+                        stateful,
+                        stateless,
+                        (*road_direction, *road_index, *lane_direction),
+                        *lane_index,
+                    )
+                } else {
+                    let acceleration = self.lane_acceleration(
+                        car_index,
+                        car,
+                        local_state,
+                        stateful,
+                        stateless,
+                        (*road_direction, *road_index, *lane_direction),
+                        &[*lane_index],
+                        *about_to_turn,
+                        position,
+                    );
+                    let location = match self.choose_lane_change(
+                        car_index,
+                        local_state,
+                        stateful,
+                        stateless,
+                        (*road_direction, *road_index, *lane_direction),
+                        *lane_index,
+                        *about_to_turn,
+                    ) {
+                        Some(to_lane_index) => ChangingLane {
+                            road_direction: *road_direction,
+                            road_index: *road_index,
+                            lane_direction: *lane_direction,
                             from_lane_index: *lane_index,
-                            to_direction,
                             to_lane_index,
-                            total_length,
-                            position: 0.0,
-                        };
-                        Some(Car {
-                            location,
-                            velocity: car.velocity,
-                            acceleration: 0.0,
-                        })
-                    } else {
-                        let mut front_objects = Vec::new();
-                        {
-                            let front_car_index = self.get_front_car(
-                                car_index,
-                                local_state,
-                                *road_direction,
-                                *road_index,
-                                *lane_direction,
-                                *lane_index,
-                            );
-                            if let Some(front_car_index) = front_car_index {
-                                let front_car = stateful.cars[front_car_index].as_ref().unwrap();
-                                let front_position = match &front_car.location {
-                                    OnLane { position, .. } => *position,
-                                    _ => unreachable!(),
-                                };
-                                let velocity = front_car.velocity;
-                                front_objects.push((front_position - position, velocity));
-                            }
-                        }
-                        {
-                            let intersection_index =
-                                stateless.city.board.lane_to_intersection_index(
-                                    *road_direction,
-                                    *road_index,
-                                    *lane_direction,
-                                );
-                            let stateful_intersection = stateful.city.board.intersections
-                                [intersection_index]
-                                .as_ref()
-                                .unwrap();
-                            let stateless_intersection = stateless.city.board.intersections
-                                [intersection_index]
-                                .as_ref()
-                                .unwrap();
-                            match stateless_intersection {
-                                stateless::Intersection::Crossroad { max_speed, .. } => {
-                                    match stateful_intersection {
-                                        stateful::Intersection::Crossroad { current, .. } => {
-                                            let from_direction = AbsoluteDirection::of_lane(
-                                                *road_direction,
-                                                *lane_direction,
-                                            )
-                                            .turn_back();
-                                            let turn_rule = *current.get(from_direction);
-                                            if about_to_turn.to_turn_rule().intersects(turn_rule) {
-                                                front_objects
-                                                    .push((road_length - position, *max_speed))
-                                            } else {
-                                                front_objects.push((road_length - position, 0.0))
-                                            }
-                                        }
-                                        _ => unreachable!(),
-                                    }
-                                }
-                                stateless::Intersection::TJunction { max_speed, .. } => {
-                                    match stateful_intersection {
-                                        stateful::Intersection::TJunction { current, .. } => {
-                                            // TODO: Fix redundant code
-                                            let from_direction = AbsoluteDirection::of_lane(
-                                                *road_direction,
-                                                *lane_direction,
-                                            )
-                                            .turn_back();
-                                            let turn_rule = *current.get(from_direction);
-                                            if about_to_turn.to_turn_rule().intersects(turn_rule) {
-                                                front_objects
-                                                    .push((road_length - position, *max_speed))
-                                            } else {
-                                                front_objects.push((road_length - position, 0.0))
-                                            }
-                                        }
-                                        _ => unreachable!(),
-                                    }
-                                }
-                                stateless::Intersection::Turn { max_speed } => {
-                                    front_objects.push((road_length - position, *max_speed))
-                                }
-                                stateless::Intersection::Straight => {
-                                    front_objects.push((road_length - position, lane.max_speed))
-                                }
-                                stateless::Intersection::End { max_speed } => {
-                                    front_objects.push((road_length - position, *max_speed))
-                                }
-                            }
-                        }
-                        let acceleration = front_objects
-                            .into_iter()
-                            .map(|(object_distance, object_velocity)| {
-                                Self::driver_acceleration(
-                                    car.velocity,
-                                    car.acceleration,
-                                    &stateless_car.driving_model,
-                                    object_distance,
-                                    object_velocity,
-                                )
-                            })
-                            .min_by(|a, b| a.partial_cmp(b).unwrap())
-                            .expect("car can not detect any object front");
-                        let acceleration = acceleration
-                            .min(stateless_car.max_acceleration)
-                            .max(-stateless_car.max_break_acceleration);
-                        Some(Car {
-                            velocity,
-                            acceleration,
-                            location: OnLane {
-                                road_direction: *road_direction,
-                                road_index: *road_index,
-                                lane_direction: *lane_direction,
-                                lane_index: *lane_index,
-                                about_to_turn: *about_to_turn,
-                                position,
-                            },
-                        })
-                    }
-                }
-                InIntersection {
-                    intersection_index,
-                    from_direction,
-                    from_lane_index,
-                    to_direction,
-                    to_lane_index,
-                    total_length,
-                    position,
-                } => {
-                    let stateless_intersection = stateless.city.board.intersections
-                        [*intersection_index]
-                        .as_ref()
-                        .unwrap();
-                    let intersection_max_speed = match stateless_intersection {
-                        stateless::Intersection::Crossroad { max_speed, .. } => Some(max_speed),
-                        stateless::Intersection::TJunction { max_speed, .. } => Some(max_speed),
-                        stateless::Intersection::Turn { max_speed } => Some(max_speed),
-                        stateless::Intersection::Straight => None,
-                        stateless::Intersection::End { max_speed } => Some(max_speed),
-                    };
-                    let proportion = position / total_length;
-                    let velocity = match intersection_max_speed {
-                        Some(max_speed) => {
-                            let velocity_proportion = proportion.min(0.5);
-                            car.velocity * (1.0 - velocity_proportion)
-                                + *max_speed * velocity_proportion
-                        }
-                        None => car.velocity,
+                            about_to_turn: *about_to_turn,
+                            position,
+                            lane_changed_proportion: 0.0,
+                        },
+                        None => OnLane {
+                            road_direction: *road_direction,
+                            road_index: *road_index,
+                            lane_direction: *lane_direction,
+                            lane_index: *lane_index,
+                            about_to_turn: *about_to_turn,
+                            position,
+                        },
                     };
-                    let position = position + car.velocity * args.dt;
-                    if position >= *total_length {
-                        let context = stateless
-                            .city
-                            .board
-                            .context_of_intersection(*intersection_index);
-                        let out_road_index = context.get(*to_direction).unwrap();
-                        let to_lane_direction =
-                            LaneDirection::absolute_in_out_to_lane(*to_direction, Out);
-                        let turn_rule = stateless
-                            .city
-                            .board
-                            .get_roads(to_direction.axis_direction())[out_road_index]
-                            .as_ref()
-                            .unwrap()
-                            .lanes_to_direction(to_lane_direction)[*to_lane_index]
-                            .direction_rule;
-                        let about_to_turn = self.random_choose_relative_direction(turn_rule);
-                        match about_to_turn {
-                            Some(about_to_turn) => {
-                                let updated_car = OnLane {
-                                    road_direction: to_direction.axis_direction(),
-                                    road_index: out_road_index,
-                                    lane_direction: to_lane_direction,
-                                    lane_index: *to_lane_index,
-                                    about_to_turn,
-                                    position: 0.0,
-                                };
-                                Some(Car {
-                                    location: updated_car,
-                                    velocity,
-                                    acceleration: 0.0,
-                                })
-                            }
-                            None => None, // remove the car
-                        }
-                    } else {
-                        Some(Car {
-                            location: InIntersection {
-                                intersection_index: *intersection_index,
-                                from_direction: *from_direction,
-                                from_lane_index: *from_lane_index,
-                                to_direction: *to_direction,
-                                to_lane_index: *to_lane_index,
-                                total_length: *total_length,
-                                position,
-                            },
-                            velocity: car.velocity,
-                            acceleration: 0.0,
-                        })
-                    }
+                    Some(Car {
+                        velocity,
+                        acceleration,
+                        location,
+                        trip: car.trip.clone(),
+                        profile: car.profile,
+                    })
                 }
-                _ => unimplemented!(),
-            }
-        } else if self.car_out_rank == rank && !*outed {
-            *outed = true;
-            match self.try_out_car(local_state, stateful, stateless) {
-                Some((road_direction, road_index, lane_direction, lane_index)) => {
-                    let turn_rule = stateless.city.board.get_roads(road_direction)[road_index]
-                        .as_ref()
-                        .unwrap()
-                        .lanes_to_direction(lane_direction)[lane_index]
-                        .direction_rule;
-                    let about_to_turn = self.random_choose_relative_direction(turn_rule);
-                    match about_to_turn {
-                        Some(about_to_turn) => {
-                            let car = stateful::Car {
-                                location: stateful::car::Location::OnLane {
-                                    road_direction,
-                                    road_index,
-                                    lane_direction,
-                                    lane_index,
-                                    position: 0.0,
-                                    about_to_turn,
-                                },
-                                acceleration: 0.0,
-                                velocity: 0.0,
-                            };
-                            log::debug!("Crate new car: {:?}", car);
-                            Some(car)
-                        }
-                        None => None,
+            }
+            ChangingLane {
+                road_direction,
+                road_index,
+                lane_direction,
+                from_lane_index,
+                to_lane_index,
+                about_to_turn,
+                position,
+                lane_changed_proportion,
+            } => {
+                let road_length = stateless.city.road_length(*road_direction, *road_index);
+                let speed_limit = stateless.city.lane_speed_limit(
+                    (*road_direction, *road_index, *lane_direction),
+                    &[*from_lane_index, *to_lane_index],
+                );
+                let max_velocity = stateless_car.max_velocity.min(speed_limit);
+                let velocity = Self::next_velocity(car, max_velocity, args.dt);
+                let position = position + car.velocity * args.dt;
+                let lane_changed_proportion =
+                    lane_changed_proportion + args.dt / stateless_car.lane_change_time;
+                // keep a safe distance to front cars of both lanes during lane changing
+                let acceleration = self.lane_acceleration(
+                    car_index,
+                    car,
+                    local_state,
+                    stateful,
+                    stateless,
+                    (*road_direction, *road_index, *lane_direction),
+                    &[*from_lane_index, *to_lane_index],
+                    *about_to_turn,
+                    position.min(road_length),
+                );
+                let location = if lane_changed_proportion >= 1.0 || position >= road_length {
+                    // lane changing finished, or forced to finish at the end of the road
+                    OnLane {
+                        road_direction: *road_direction,
+                        road_index: *road_index,
+                        lane_direction: *lane_direction,
+                        lane_index: *to_lane_index,
+                        about_to_turn: *about_to_turn,
+                        position,
+                    }
+                } else {
+                    ChangingLane {
+                        road_direction: *road_direction,
+                        road_index: *road_index,
+                        lane_direction: *lane_direction,
+                        from_lane_index: *from_lane_index,
+                        to_lane_index: *to_lane_index,
+                        about_to_turn: *about_to_turn,
+                        position,
+                        lane_changed_proportion,
                     }
+                };
+                Some(Car {
+                    velocity,
+                    acceleration,
+                    location,
+                    trip: car.trip.clone(),
+                    profile: car.profile,
+                })
+            }
+            InIntersection {
+                intersection_index,
+                from_direction,
+                from_lane_index,
+                to_direction,
+                to_lane_index,
+                total_length,
+                position,
+            } => {
+                let context = stateless
+                    .city
+                    .board
+                    .context_of_intersection(*intersection_index);
+                let out_road_index = context.get(*to_direction).unwrap();
+                let to_lane_direction = LaneDirection::absolute_in_out_to_lane(*to_direction, Out);
+                let stateless_intersection = stateless.city.board.intersections
+                    [*intersection_index]
+                    .as_ref()
+                    .unwrap();
+                let speed_limit = match stateless_intersection {
+                    stateless::Intersection::Crossroad { max_speed, .. } => *max_speed,
+                    stateless::Intersection::TJunction { max_speed, .. } => *max_speed,
+                    stateless::Intersection::Turn { max_speed } => *max_speed,
+                    stateless::Intersection::Straight => stateless.city.lane_speed_limit(
+                        (
+                            to_direction.axis_direction(),
+                            out_road_index,
+                            to_lane_direction,
+                        ),
+                        &[*to_lane_index],
+                    ),
+                    stateless::Intersection::End { max_speed } => *max_speed,
+                };
+                let acceleration = self.intersection_acceleration(
+                    car_index,
+                    local_state,
+                    stateful,
+                    stateless,
+                    speed_limit,
+                );
+                let max_velocity = stateless_car.max_velocity.min(speed_limit);
+                let velocity = Self::next_velocity(car, max_velocity, args.dt);
+                let position = position + car.velocity * args.dt;
+                if position >= *total_length {
+                    let out_road = (
+                        to_direction.axis_direction(),
+                        out_road_index,
+                        to_lane_direction,
+                    );
+                    let updated_car = OnLane {
+                        road_direction: to_direction.axis_direction(),
+                        road_index: out_road_index,
+                        lane_direction: to_lane_direction,
+                        lane_index: *to_lane_index,
+                        about_to_turn: Self::planned_turn(stateless, out_road, &car.trip, &mut rng),
+                        position: 0.0,
+                    };
+                    Some(Car {
+                        location: updated_car,
+                        velocity,
+                        acceleration: 0.0,
+                        trip: car.trip.clone(),
+                        profile: car.profile,
+                    })
+                } else {
+                    Some(Car {
+                        location: InIntersection {
+                            intersection_index: *intersection_index,
+                            from_direction: *from_direction,
+                            from_lane_index: *from_lane_index,
+                            to_direction: *to_direction,
+                            to_lane_index: *to_lane_index,
+                            total_length: *total_length,
+                            position,
+                        },
+                        velocity,
+                        acceleration,
+                        trip: car.trip.clone(),
+                        profile: car.profile,
+                    })
                 }
-                None => None,
             }
-        } else {
-            None
         }
     }
 
-    pub fn driver_acceleration(
+    /// Spawn a new car with the ID `car_index` from the source `source_index`,
+    /// with a random profile of the catalog.
+    ///
+    /// Return `None` if no out lane of the source is available.
+    pub fn spawn_car(
+        &self,
+        car_index: CarIndex,
+        source_index: usize,
+        local_state: &ProcessLocalState,
+        stateful: &stateful::Model,
+        stateless: &stateless::Model,
+    ) -> Option<stateful::Car> {
+        if stateless.cars.is_empty() {
+            return None;
+        }
+        let mut rng = rng::stream_rng(
+            stateless.seed,
+            Stream::CarUpdate,
+            car_index as u64,
+            stateful.step,
+        );
+        let profile = rng.gen_range(0..stateless.cars.len());
+        let ((road_direction, road_index, lane_direction, lane_index), trip) =
+            self.try_out_car(local_state, stateful, stateless, source_index, &mut rng)?;
+        let car = stateful::Car {
+            location: stateful::car::Location::OnLane {
+                road_direction,
+                road_index,
+                lane_direction,
+                lane_index,
+                position: 0.0,
+                about_to_turn: Self::planned_turn(
+                    stateless,
+                    (road_direction, road_index, lane_direction),
+                    &trip,
+                    &mut rng,
+                ),
+            },
+            acceleration: 0.0,
+            velocity: 0.0,
+            trip,
+            profile,
+        };
+        log::debug!("Crate new car: {:?}", car);
+        Some(car)
+    }
+
+    fn next_velocity(car: &stateful::Car, max_velocity: f64, dt: f64) -> f64 {
+        (car.velocity + car.acceleration * dt)
+            .min(max_velocity)
+            .max(0.0)
+    }
+
+    /// Move a car at the end of a lane into the intersection, to the out lane
+    /// chosen by `ProcessLocalState::out_lane`, taking the next turn of its
+    /// route.
+    ///
+    /// Return `None` if the car arrives at its destination, or can not reach
+    /// its destination from the lane, and should be removed.
+    #[allow(clippy::too_many_arguments)]
+    fn enter_intersection(
+        &self,
         velocity: f64,
-        _acceleration: f64,
-        driving_model: &DrivingModel,
-        front_distance: f64,
-        front_velocity: f64,
+        car: &stateful::Car,
+        local_state: &ProcessLocalState,
+        stateful: &stateful::Model,
+        stateless: &stateless::Model,
+        (road_direction, road_index, lane_direction): (AxisDirection, RoadIndex, LaneDirection),
+        lane_index: LaneIndex,
+    ) -> Option<stateful::Car> {
+        let lane = &stateless.city.board.get_roads(road_direction)[road_index]
+            .as_ref()
+            .unwrap()
+            .lanes_to_direction(lane_direction)[lane_index];
+        let mut trip = car.trip.clone();
+        let about_to_turn = trip.route.pop_front()?;
+        let about_to_turn = if lane.direction_rule.contains(about_to_turn.to_turn_rule()) {
+            about_to_turn
+        } else {
+            // failed to reach a lane allowing the turn, find another way
+            let (about_to_turn, route) = routing::shortest_route_turning(
+                &stateless.city,
+                (road_direction, road_index, lane_direction),
+                lane.direction_rule,
+                trip.destination,
+            )?;
+            trip.route = route.turns;
+            about_to_turn
+        };
+        let intersection_index = stateless.city.board.lane_to_intersection_index(
+            road_direction,
+            road_index,
+            lane_direction,
+        );
+        let driver_direction = AbsoluteDirection::of_lane(road_direction, lane_direction);
+        let to_direction = driver_direction.turn(about_to_turn);
+        let from_direction = driver_direction.turn_back();
+        let to_lane_index = local_state
+            .out_lane(stateless, &stateful.cars, intersection_index, to_direction)
+            .expect("no way to turn");
+        let total_length = stateless
+            .city
+            .intersection_path_total_length(
+                intersection_index,
+                from_direction,
+                lane_index,
+                to_direction,
+                to_lane_index,
+            )
+            .unwrap();
+        let location = stateful::car::Location::InIntersection {
+            intersection_index,
+            from_direction,
+            from_lane_index: lane_index,
+            to_direction,
+            to_lane_index,
+            total_length,
+            position: 0.0,
+        };
+        Some(Car {
+            location,
+            velocity,
+            acceleration: 0.0,
+            trip,
+            profile: car.profile,
+        })
+    }
+
+    /// Re-plan the route of an informed driver on a lane with live travel
+    /// times, every `reroute_interval` steps.
+    ///
+    /// Drivers re-plan in different steps to spread the load. Travel times
+    /// are estimated from velocities of all cars of the city summed at the
+    /// start of the interval. Return `None` if the car keeps its route.
+    fn reroute(
+        &self,
+        car_index: CarIndex,
+        car: &stateful::Car,
+        stateful: &stateful::Model,
+        stateless: &stateless::Model,
+    ) -> Option<stateful::Car> {
+        use crate::model::stateful::car::Location::*;
+        let interval = self.settings.reroute_interval;
+        if interval == 0
+            || !stateless.car(car).informed
+            || stateful.step % interval != car_index as u64 % interval
+            || car.trip.route.is_empty()
+        {
+            return None;
+        }
+        let road = match car.location {
+            OnLane {
+                road_direction,
+                road_index,
+                lane_direction,
+                ..
+            }
+            | ChangingLane {
+                road_direction,
+                road_index,
+                lane_direction,
+                ..
+            } => (road_direction, road_index, lane_direction),
+            InIntersection { .. } => return None,
+        };
+        let road_velocities = self.road_velocities.as_ref()?;
+        let route =
+            routing::shortest_route_by(&stateless.city, road, car.trip.destination, |road| {
+                road_velocities.travel_time(&stateless.city, road)
+            })?;
+        let next_turn = *route.turns.front()?;
+        if route.turns == car.trip.route {
+            return None;
+        }
+        let mut car = car.clone();
+        car.trip.route = route.turns;
+        match &mut car.location {
+            OnLane { about_to_turn, .. } | ChangingLane { about_to_turn, .. } => {
+                *about_to_turn = next_turn
+            }
+            InIntersection { .. } => unreachable!(),
+        }
+        Some(car)
+    }
+
+    /// The turn a car plans at the end of a road: the next turn of its route,
+    /// or a random turn allowed by the road if the road leads to the
+    /// destination.
+    fn planned_turn<R: Rng>(
+        stateless: &stateless::Model,
+        (road_direction, road_index, lane_direction): (AxisDirection, RoadIndex, LaneDirection),
+        trip: &Trip,
+        rng: &mut R,
+    ) -> RelativeDirection {
+        if let Some(turn) = trip.route.front() {
+            return *turn;
+        }
+        let turn_rule = stateless.city.board.get_roads(road_direction)[road_index]
+            .as_ref()
+            .unwrap()
+            .turn_rule_to_direction(lane_direction);
+        Self::random_choose_relative_direction(turn_rule, rng).unwrap_or(RelativeDirection::Front)
+    }
+
+    /// Velocity of the stop line at the end of a lane as a front object.
+    ///
+    /// The stop line is treated as a still object if the car is not allowed
+    /// to pass the intersection, or if the car turns left permissively and
+    /// does not accept the gap to opposing traffic.
+    #[allow(clippy::too_many_arguments)]
+    fn stop_line_velocity(
+        &self,
+        car_index: CarIndex,
+        local_state: &ProcessLocalState,
+        stateful: &stateful::Model,
+        stateless: &stateless::Model,
+        (road_direction, road_index, lane_direction): (AxisDirection, RoadIndex, LaneDirection),
+        about_to_turn: RelativeDirection,
+        speed_limit: f64,
     ) -> f64 {
-        match driving_model {
-            DrivingModel::Normal {
-                min_cushion,
-                cushion_velocity_factor,
-                prediction_time,
-            } => {
-                let aim_cushion = min_cushion + cushion_velocity_factor * front_velocity;
-                let dx = front_distance - aim_cushion; // if dx is greater than 0, the car should go faster than front_velocity
-                let aim_average_velocity = dx / *prediction_time;
-                (aim_average_velocity + front_velocity - velocity) * 2.0 / *prediction_time
+        let intersection_index = stateless.city.board.lane_to_intersection_index(
+            road_direction,
+            road_index,
+            lane_direction,
+        );
+        let stateful_intersection = stateful.city.board.intersections[intersection_index]
+            .as_ref()
+            .unwrap();
+        let stateless_intersection = stateless.city.board.intersections[intersection_index]
+            .as_ref()
+            .unwrap();
+        let from_direction = AbsoluteDirection::of_lane(road_direction, lane_direction).turn_back();
+        match (stateless_intersection, stateful_intersection) {
+            (
+                stateless::Intersection::Crossroad { max_speed, .. },
+                stateful::Intersection::Crossroad { current, .. },
+            )
+            | (
+                stateless::Intersection::TJunction { max_speed, .. },
+                stateful::Intersection::TJunction { current, .. },
+            ) => {
+                let turn_rule = *current.get(from_direction);
+                let opposing_rule = *current.get(from_direction.turn_back());
+                let allowed = about_to_turn.to_turn_rule().intersects(turn_rule);
+                let permissive = matches!(
+                    about_to_turn,
+                    RelativeDirection::Left | RelativeDirection::Back
+                ) && opposing_rule.intersects(TurnRule::FRONT | TurnRule::RIGHT);
+                let gap_rejected = permissive
+                    && !self.accept_gap(
+                        car_index,
+                        local_state,
+                        stateful,
+                        stateless,
+                        (road_direction, road_index, lane_direction),
+                    );
+                if allowed && !gap_rejected {
+                    *max_speed
+                } else {
+                    0.0
+                }
             }
+            (stateless::Intersection::Crossroad { .. }, _) => unreachable!(),
+            (stateless::Intersection::TJunction { .. }, _) => unreachable!(),
+            (stateless::Intersection::Turn { max_speed }, _) => *max_speed,
+            (stateless::Intersection::Straight, _) => speed_limit,
+            (stateless::Intersection::End { max_speed }, _) => *max_speed,
         }
     }
 
+    /// Calculate the acceleration of a car on lanes of a road.
+    ///
+    /// Front cars on all `lane_indices` and the intersection at the end of the
+    /// road are considered as front objects.
+    #[allow(clippy::too_many_arguments)]
+    fn lane_acceleration(
+        &self,
+        car_index: CarIndex,
+        car: &stateful::Car,
+        local_state: &ProcessLocalState,
+        stateful: &stateful::Model,
+        stateless: &stateless::Model,
+        (road_direction, road_index, lane_direction): (AxisDirection, RoadIndex, LaneDirection),
+        lane_indices: &[LaneIndex],
+        about_to_turn: RelativeDirection,
+        position: f64,
+    ) -> f64 {
+        let stateless_car = stateless.car(&stateful.cars[car_index]);
+        let road_length = stateless.city.road_length(road_direction, road_index);
+        let speed_limit = stateless
+            .city
+            .lane_speed_limit((road_direction, road_index, lane_direction), lane_indices);
+        let mut front_objects = Vec::new();
+        for lane_index in lane_indices {
+            let front_car_index = self.get_front_car(
+                car_index,
+                local_state,
+                road_direction,
+                road_index,
+                lane_direction,
+                *lane_index,
+            );
+            if let Some(front_car_index) = front_car_index {
+                let front_car = &stateful.cars[front_car_index];
+                let front_rear_position = front_car.location.lane_position().unwrap()
+                    - stateless.car(&stateful.cars[front_car_index]).length;
+                let velocity = front_car.velocity;
+                front_objects.push((front_rear_position - position, velocity));
+            } else if let Some((distance, leader_index)) = local_state.path_leader(
+                stateless,
+                &stateful.cars,
+                (road_direction, road_index, lane_direction),
+                *lane_index,
+                about_to_turn,
+            ) {
+                // look ahead through the intersection
+                let velocity = stateful.cars[leader_index].velocity;
+                front_objects.push((road_length - position + distance, velocity));
+            }
+        }
+        front_objects.push((
+            road_length - position,
+            self.stop_line_velocity(
+                car_index,
+                local_state,
+                stateful,
+                stateless,
+                (road_direction, road_index, lane_direction),
+                about_to_turn,
+                speed_limit,
+            ),
+        ));
+        let acceleration = front_objects
+            .into_iter()
+            .map(|(object_distance, object_velocity)| {
+                self.driver_acceleration(car, object_distance, object_velocity, speed_limit)
+            })
+            .min_by(|a, b| a.partial_cmp(b).unwrap())
+            .expect("car can not detect any object front");
+        acceleration
+            .min(stateless_car.max_acceleration)
+            .max(-stateless_car.max_break_acceleration)
+    }
+
+    /// Acceleration given by the driving model of a car
+    pub fn driver_acceleration(
+        &self,
+        car: &stateful::Car,
+        gap: f64,
+        leader_velocity: f64,
+        speed_limit: f64,
+    ) -> f64 {
+        let situation = FollowingSituation {
+            velocity: car.velocity,
+            acceleration: car.acceleration,
+            gap,
+            leader_velocity,
+            speed_limit,
+        };
+        self.car_following_models
+            .acceleration(car.profile, &situation)
+    }
+
     fn get_front_car(
         &self,
         current_car: CarIndex,
@@ -553,7 +1376,10 @@ impl UpdateController {
         None
     }
 
-    fn random_choose_relative_direction(&self, turn_rule: TurnRule) -> Option<RelativeDirection> {
+    fn random_choose_relative_direction<R: Rng>(
+        turn_rule: TurnRule,
+        rng: &mut R,
+    ) -> Option<RelativeDirection> {
         use crate::model::common::RelativeDirection::*;
         let all_rule = [
             TurnRule::FRONT,
@@ -568,7 +1394,6 @@ impl UpdateController {
         match enabled_rule.len() {
             0 => None,
             len => {
-                let mut rng = rand::thread_rng();
                 let rule = enabled_rule[rng.gen_range(0..len)];
                 Some(match *rule {
                     TurnRule::FRONT => Front,
@@ -581,65 +1406,200 @@ impl UpdateController {
         }
     }
 
-    pub fn try_out_car(
+    /// Choose a random sink as the destination and an available out lane of
+    /// a source to start the trip.
+    ///
+    /// Lanes allowing the first turn of their route are preferred, then
+    /// shorter routes. Return `None` if no available lane leads to the
+    /// destination.
+    pub fn try_out_car<R: Rng>(
         &self,
         local_state: &ProcessLocalState,
         _stateful: &stateful::Model,
         stateless: &stateless::Model,
-    ) -> Option<(AxisDirection, RoadIndex, LaneDirection, LaneIndex)> {
+        source_index: usize,
+        rng: &mut R,
+    ) -> Option<((AxisDirection, RoadIndex, LaneDirection, LaneIndex), Trip)> {
         log::trace!("try_out_car called");
-        let context = stateless
+        let origin = stateless.city.sources[source_index].intersection;
+        let destinations = stateless
             .city
-            .board
-            .context_of_intersection(stateless.city.car_out_intersection);
-        for direction in AbsoluteDirection::directions() {
-            let lanes_availability = local_state
-                .car_out_intersection_lane_out_availability
-                .get(*direction);
-            for (lane_index, availability) in lanes_availability.iter().enumerate() {
-                if *availability {
-                    let road_index = context.get(*direction).unwrap();
-                    let car_out_parameter = (
-                        direction.axis_direction(),
-                        road_index,
-                        LaneDirection::absolute_in_out_to_lane(*direction, InOutDirection::Out),
-                        lane_index,
-                    );
-                    log::debug!("car out parameter: {:?}", car_out_parameter);
-                    return Some(car_out_parameter);
+            .sinks
+            .iter()
+            .copied()
+            .filter(|index| *index != origin)
+            .collect::<Vec<_>>();
+        if destinations.is_empty() {
+            return None;
+        }
+        let destination = destinations[rng.gen_range(0..destinations.len())];
+        let context = stateless.city.board.context_of_intersection(origin);
+        let availability = &local_state.source_lane_out_availability[source_index];
+        // lanes of a road share the route to the destination
+        let routes = AbsoluteDirection::directions()
+            .filter(|direction| availability.get(**direction).contains(&true))
+            .filter_map(|direction| {
+                let road = (
+                    direction.axis_direction(),
+                    (*context.get(*direction))?,
+                    LaneDirection::absolute_in_out_to_lane(*direction, InOutDirection::Out),
+                );
+                let route = routing::shortest_route(&stateless.city, road, destination)?;
+                Some((*direction, road, route))
+            })
+            .collect::<Vec<_>>();
+        let mut candidates = Vec::new();
+        for (direction, (road_direction, road_index, lane_direction), route) in routes.iter() {
+            let lanes = stateless.city.board.get_roads(*road_direction)[*road_index]
+                .as_ref()
+                .unwrap()
+                .lanes_to_direction(*lane_direction);
+            for (lane_index, available) in availability.get(*direction).iter().enumerate() {
+                if !*available {
+                    continue;
                 }
+                let allow_turn = match route.turns.front() {
+                    Some(turn) => lanes[lane_index]
+                        .direction_rule
+                        .contains(turn.to_turn_rule()),
+                    None => true,
+                };
+                candidates.push((
+                    (*road_direction, *road_index, *lane_direction, lane_index),
+                    route,
+                    allow_turn,
+                ));
             }
         }
-        None
+        let (car_out_parameter, route, _) = candidates.into_iter().min_by(
+            |(_, route, allow_turn), (_, other_route, other_allow_turn)| {
+                other_allow_turn
+                    .cmp(allow_turn)
+                    .then(route.cost.partial_cmp(&other_route.cost).unwrap())
+            },
+        )?;
+        log::debug!(
+            "car out parameter: {:?}, destination: {:?}",
+            car_out_parameter,
+            destination
+        );
+        let trip = Trip {
+            origin,
+            destination,
+            route: route.turns.clone(),
+        };
+        Some((car_out_parameter, trip))
     }
 
+    /// Update intersections owned by this process, and sources in the root.
+    ///
+    /// Changed signals are sent to the root for the view, and the root sends
+    /// changed waiting cars of sources to all processes.
     pub fn update_city<Comm>(
         &mut self,
         root: Rank,
         communicator: Comm,
-        stateful: &mut stateful::City,
-        stateless: &stateless::City,
+        stateful_model: &mut stateful::Model,
+        stateless_model: &stateless::Model,
         args: UpdateArgs,
     ) where
-        Comm: CommunicatorCollectives,
+        Comm: CommunicatorCollectives + Clone,
     {
-        if communicator.rank() == root {
-            // Update intersection first
-            for (stateful_intersection, stateless_intersection) in stateful
-                .board
-                .intersections
+        let rank = communicator.rank();
+        let stateful = &mut stateful_model.city;
+        let stateless = &stateless_model.city;
+        let decomposition = self.decomposition.clone().unwrap();
+        let mut signal_changes = Vec::new();
+        for ((index, stateful_intersection), stateless_intersection) in stateful
+            .board
+            .intersections
+            .enumerate_mut()
+            .zip(stateless.board.intersections.iter())
+        {
+            if decomposition.owner(index) != rank {
+                continue;
+            }
+            if let Some(stateful_intersection) = stateful_intersection.as_mut() {
+                let stateless_intersection = stateless_intersection.as_ref().unwrap();
+                let previous = stateful_intersection.current().copied();
+                self.update_intersection(stateful_intersection, stateless_intersection, args);
+                stateful_intersection.update_current(stateless_intersection);
+                match stateful_intersection.current() {
+                    Some(current) if previous.as_ref() != Some(current) => {
+                        signal_changes.push(sync::signal_change(index, *current))
+                    }
+                    _ => (),
+                }
+            }
+        }
+        let (gathered, traffic) =
+            communication::bincode_gather_varcount(communicator.clone(), root, &signal_changes)
+                .unwrap();
+        self.traffic.city += traffic;
+        if let Some(gathered) = gathered {
+            for (other, changes) in gathered.iter().enumerate() {
+                if other as Rank != rank {
+                    sync::apply_signal_changes(stateful, changes);
+                }
+            }
+        }
+
+        if rank == root {
+            if self.synced_sources.is_none() {
+                self.synced_sources = Some(stateful.sources.clone());
+            }
+            for (source_index, (stateful_source, stateless_source)) in stateful
+                .sources
                 .iter_mut()
-                .zip(stateless.board.intersections.iter())
+                .zip(stateless.sources.iter())
+                .enumerate()
             {
-                if let Some(stateful_intersection) = stateful_intersection.as_mut() {
-                    let stateless_intersection = stateless_intersection.as_ref().unwrap();
-                    self.update_intersection(stateful_intersection, stateless_intersection, args);
-                    stateful_intersection.update_current(stateless_intersection);
-                }
+                Self::update_source(
+                    stateful_source,
+                    stateless_source,
+                    stateless_model.seed,
+                    source_index,
+                    stateful.time,
+                    args,
+                );
             }
+            stateful.time += args.dt;
+        }
+        // only changes since the last synchronization are sent
+        let mut delta = CityDelta::default();
+        if let Some(synced) = self.synced_sources.as_mut() {
+            delta = CityDelta::between(synced, stateful);
+            *synced = stateful.sources.clone();
         }
         let root_process = communicator.process_at_rank(root);
-        communication::bincode_broadcast(communicator.rank(), root_process, stateful).unwrap();
+        self.traffic.city +=
+            communication::bincode_broadcast(rank, root_process, &mut delta).unwrap();
+        if rank != root {
+            delta.apply(stateful);
+        }
+    }
+
+    /// Add arrived cars to the waiting cars of a source.
+    ///
+    /// The arrival rate is integrated over time, a car arrives whenever the
+    /// integral reaches an amount of work drawn by `Arrival::work`, the work
+    /// to the first arrival is drawn when the source is created.
+    fn update_source(
+        stateful: &mut stateful::Source,
+        stateless: &stateless::Source,
+        seed: u64,
+        source_index: usize,
+        time: f64,
+        UpdateArgs { dt }: UpdateArgs,
+    ) {
+        stateful.remaining -= stateless.arrival.rate(time) / 3600.0 * dt;
+        while stateful.remaining <= 0.0 {
+            stateful.waiting += 1;
+            stateful.arrivals += 1;
+            stateful.remaining += stateless
+                .arrival
+                .work(seed, source_index, stateful.arrivals);
+        }
     }
 
     fn update_intersection(
@@ -707,3 +1667,423 @@ impl UpdateController {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        communication::with_world,
+        model::generate::{self, ModelGenerationSettings},
+    };
+
+    const ROOT: Rank = 0;
+
+    fn model() -> Model {
+        generate::generate_model(ModelGenerationSettings::from_iter(&["test", "--seed", "2"]))
+            .unwrap()
+    }
+
+    #[test]
+    fn restore_checkpoint() {
+        let path = std::env::temp_dir().join(format!(
+            "mpi-traffic-restore-checkpoint-{}.checkpoint",
+            std::process::id()
+        ));
+        let args = UpdateArgs { dt: 0.1 };
+        for engine in &["continuous", "cellular-automaton"] {
+            let settings = || UpdateSettings::from_iter(&["test", "--update-engine", engine]);
+            let Model {
+                stateless,
+                mut stateful,
+            } = model();
+            let mut controller =
+                UpdateController::new(settings(), &stateless, &stateful, Default::default())
+                    .unwrap();
+            let resumed = with_world(|world| {
+                // in the middle of an interval of rerouting
+                for _ in 0..155 {
+                    controller.update(ROOT, world, &mut stateful, &stateless, args);
+                }
+                let checkpoint = controller.checkpoint(ROOT, world, &stateful, &stateless);
+                checkpoint.unwrap().save(&path).unwrap();
+                for _ in 0..100 {
+                    controller.update(ROOT, world, &mut stateful, &stateless, args);
+                }
+                let checkpoint = Checkpoint::load(&path).unwrap();
+                std::fs::remove_file(&path).unwrap();
+                let mut restored = UpdateController::new(
+                    settings(),
+                    &stateless,
+                    &checkpoint.model.stateful,
+                    checkpoint.controller,
+                )
+                .unwrap();
+                let mut resumed = checkpoint.model.stateful;
+                for _ in 0..100 {
+                    restored.update(ROOT, world, &mut resumed, &stateless, args);
+                }
+                resumed
+            });
+            assert!(!stateful.cars.is_empty());
+            assert_eq!(
+                bincode::serialize(&resumed).unwrap(),
+                bincode::serialize(&stateful).unwrap()
+            );
+        }
+    }
+
+    /// Cars owned by simulated processes, in the order of ranks
+    type Owned = Vec<Pool<Car>>;
+
+    /// Split cars among `size` simulated processes by their owners in
+    /// `decomposition`, as `update_cars` does
+    fn distribute(
+        decomposition: &Decomposition,
+        stateless: &stateless::Model,
+        cars: &Pool<Car>,
+        size: Rank,
+    ) -> Owned {
+        let board = &stateless.city.board;
+        (0..size)
+            .map(|rank| {
+                let mut owned = cars.clone();
+                owned.retain(|_, car| decomposition.car_owner(board, &car.location) == rank);
+                owned
+            })
+            .collect()
+    }
+
+    fn gather(owned: &[Pool<Car>], next_id: usize) -> Pool<Car> {
+        let cars = owned.iter().flat_map(|cars| cars.entries().iter().cloned());
+        Pool::from_entries(cars.collect(), next_id)
+    }
+
+    /// Deliver items sent by each simulated process in the order of ranks
+    fn deliver<T>(sends: Vec<decomposition::Sends<T>>) -> Vec<Vec<T>> {
+        let mut received = sends.iter().map(|_| Vec::new()).collect::<Vec<_>>();
+        for send in sends {
+            for (rank, items) in send.into_iter().enumerate() {
+                received[rank].extend(items);
+            }
+        }
+        received
+    }
+
+    /// Update cars of simulated processes like `update_cars`, exchanging
+    /// halos of blocks of `before` and migrating cars to their owners in
+    /// `after`. The city and the step are taken from `stateful`.
+    fn simulated_step(
+        controller: &UpdateController,
+        stateless: &stateless::Model,
+        stateful: &stateful::Model,
+        (before, after): (&Decomposition, &Decomposition),
+        owned: Owned,
+    ) -> Owned {
+        let size = owned.len() as Rank;
+        let board = &stateless.city.board;
+        let next_id = stateful.cars.next_id();
+        let halos = deliver(
+            owned
+                .iter()
+                .enumerate()
+                .map(|(rank, cars)| before.halo(board, rank as Rank, size, cars))
+                .collect(),
+        );
+        let mut kept = Vec::new();
+        let mut migrations = Vec::new();
+        for ((rank, cars), halo) in owned.into_iter().enumerate().zip(halos) {
+            let ids = cars.ids().collect::<Vec<_>>();
+            let mut local = stateful.clone();
+            let mut known = cars.into_entries();
+            known.extend(halo);
+            local.cars = Pool::from_entries(known, next_id);
+            let local_state =
+                ProcessLocalState::generate(&stateless.city, &local.cars, &stateless.cars);
+            let updated = ids
+                .into_iter()
+                .filter_map(|car_index| {
+                    let args = UpdateArgs { dt: 0.1 };
+                    controller
+                        .update_car(car_index, &local_state, &local, stateless, args)
+                        .map(|car| (car_index, car))
+                })
+                .collect();
+            let (cars, send) = after.migration(board, rank as Rank, size, updated);
+            kept.push(cars);
+            migrations.push(send);
+        }
+        kept.into_iter()
+            .zip(deliver(migrations))
+            .map(|(mut cars, migrated)| {
+                cars.extend(migrated);
+                Pool::from_entries(cars, next_id)
+            })
+            .collect()
+    }
+
+    /// A model with cars all over the city and an update controller
+    fn running_model() -> (UpdateController, stateless::Model, stateful::Model) {
+        let Model {
+            stateless,
+            stateful,
+        } = model();
+        let mut stateful = stateful;
+        let settings = || UpdateSettings::from_iter(&["test", "--reroute-interval", "0"]);
+        let mut controller =
+            UpdateController::new(settings(), &stateless, &stateful, Default::default()).unwrap();
+        with_world(|world| {
+            for _ in 0..150 {
+                let args = UpdateArgs { dt: 0.1 };
+                controller.update(ROOT, world, &mut stateful, &stateless, args);
+            }
+        });
+        assert!(stateful.cars.len() > 10);
+        let controller =
+            UpdateController::new(settings(), &stateless, &stateful, Default::default()).unwrap();
+        (controller, stateless, stateful)
+    }
+
+    #[test]
+    fn rebalance_keeps_trajectories() {
+        let (controller, stateless, mut stateful) = running_model();
+        let board = &stateless.city.board;
+        let shape = board.shape();
+        let single = Decomposition::new(shape, 1);
+        let mut decomposition = Decomposition::new(shape, 4);
+        assert_eq!(decomposition.block_number(), 4);
+        let mut reference = vec![stateful.cars.clone()];
+        let mut owned = distribute(&decomposition, &stateless, &stateful.cars, 4);
+        let mut moved = 0;
+        for step in 1..=40 {
+            // loads of a corner of the board pull bounds of blocks to it
+            let rebalanced = match step {
+                10 | 30 => {
+                    let mut load = Load::new(shape);
+                    load.add((0, 0), 100.0);
+                    decomposition.rebalanced(&load)
+                }
+                20 => {
+                    let mut load = Load::new(shape);
+                    load.add((shape.0 - 1, shape.1 - 1), 100.0);
+                    decomposition.rebalanced(&load)
+                }
+                _ => decomposition.clone(),
+            };
+            reference = simulated_step(
+                &controller,
+                &stateless,
+                &stateful,
+                (&single, &single),
+                reference,
+            );
+            owned = simulated_step(
+                &controller,
+                &stateless,
+                &stateful,
+                (&decomposition, &rebalanced),
+                owned,
+            );
+            stateful.step += 1;
+            for (rank, cars) in owned.iter().enumerate() {
+                for (_, car) in cars.iter() {
+                    assert_eq!(rebalanced.car_owner(board, &car.location), rank as Rank);
+                    if decomposition.car_owner(board, &car.location) != rank as Rank {
+                        moved += 1;
+                    }
+                }
+            }
+            assert_eq!(
+                bincode::serialize(&gather(&owned, stateful.cars.next_id())).unwrap(),
+                bincode::serialize(&reference[0]).unwrap()
+            );
+            decomposition = rebalanced;
+        }
+        assert!(moved > 0);
+    }
+
+    #[test]
+    fn restore_checkpoint_on_other_processes() {
+        let path = std::env::temp_dir().join(format!(
+            "mpi-traffic-restore-other-processes-{}.checkpoint",
+            std::process::id()
+        ));
+        let (controller, stateless, mut stateful) = running_model();
+        let shape = stateless.city.board.shape();
+        let single = Decomposition::new(shape, 1);
+        let mut reference = vec![stateful.cars.clone()];
+        let run = |controller: &UpdateController,
+                   stateful: &mut stateful::Model,
+                   decomposition: &Decomposition,
+                   mut owned: Owned,
+                   mut reference: Owned| {
+            for _ in 0..20 {
+                let blocks = (decomposition, decomposition);
+                owned = simulated_step(controller, &stateless, stateful, blocks, owned);
+                let blocks = (&single, &single);
+                reference = simulated_step(controller, &stateless, stateful, blocks, reference);
+                stateful.step += 1;
+            }
+            (owned, reference)
+        };
+
+        let decomposition = Decomposition::new(shape, 4);
+        let owned = distribute(&decomposition, &stateless, &stateful.cars, 4);
+        let (owned, resumed_reference) =
+            run(&controller, &mut stateful, &decomposition, owned, reference);
+        reference = resumed_reference;
+        // cars gathered from their owners into the checkpoint
+        let mut saved = stateful.clone();
+        saved.cars = gather(&owned, stateful.cars.next_id());
+        Checkpoint {
+            model: Model {
+                stateless: stateless.clone(),
+                stateful: saved,
+            },
+            controller: Default::default(),
+        }
+        .save(&path)
+        .unwrap();
+        let checkpoint = Checkpoint::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let restored = UpdateController::new(
+            UpdateSettings::from_iter(&["test", "--reroute-interval", "0"]),
+            &stateless,
+            &checkpoint.model.stateful,
+            checkpoint.controller,
+        )
+        .unwrap();
+        let mut resumed = checkpoint.model.stateful;
+        let decomposition = Decomposition::new(shape, 3);
+        assert_eq!(decomposition.block_number(), 3);
+        let owned = distribute(&decomposition, &stateless, &resumed.cars, 3);
+        assert!(owned.iter().filter(|cars| !cars.is_empty()).count() > 1);
+        let (owned, reference) = run(&restored, &mut resumed, &decomposition, owned, reference);
+        assert!(!reference[0].is_empty());
+        assert_eq!(
+            bincode::serialize(&gather(&owned, resumed.cars.next_id())).unwrap(),
+            bincode::serialize(&reference[0]).unwrap()
+        );
+    }
+
+    /// Run a source created at time 0 for `duration` seconds, return the
+    /// times of arrivals
+    fn arrival_times(arrival: stateless::Arrival, duration: f64) -> Vec<f64> {
+        let args = UpdateArgs { dt: 0.1 };
+        let source = stateless::Source {
+            intersection: (0, 0),
+            arrival,
+        };
+        let mut stateful = stateful::Source::new(&source.arrival, 0, 0);
+        let mut times = Vec::new();
+        for step in 0..(duration / args.dt) as usize {
+            let time = step as f64 * args.dt;
+            UpdateController::update_source(&mut stateful, &source, 0, 0, time, args);
+            while times.len() < stateful.waiting {
+                times.push(time);
+            }
+        }
+        times
+    }
+
+    #[test]
+    fn no_arrival_at_start() {
+        use stateless::Arrival;
+        assert!(arrival_times(Arrival::Poisson { rate: 0.0 }, 100.0).is_empty());
+        let times = arrival_times(Arrival::Constant { rate: 3600.0 }, 10.0);
+        assert_eq!(times.len(), 9);
+        assert!(times[0] > 0.5);
+        let profile = Arrival::Profile {
+            rates: vec![(50.0, 3600.0)],
+            cycle: None,
+        };
+        let times = arrival_times(profile, 100.0);
+        assert!(!times.is_empty());
+        assert!(times[0] >= 50.0);
+        assert!(arrival_times(Arrival::Poisson { rate: 3600.0 }, 100.0)[0] > 0.0);
+    }
+
+    fn press(controller: &mut Controller, model: &mut Model, key: Key) {
+        let input = Input::Button(ButtonArgs {
+            state: ButtonState::Press,
+            button: Button::Keyboard(key),
+            scancode: None,
+        });
+        controller.input(
+            &mut Info::new(),
+            &mut model.stateful,
+            &model.stateless,
+            input,
+        );
+    }
+
+    #[test]
+    fn single_step_and_reset() {
+        let mut model = model();
+        let initial = bincode::serialize(&model.stateful).unwrap();
+        let update_controller = UpdateController::new(
+            UpdateSettings::from_iter(&["test"]),
+            &model.stateless,
+            &model.stateful,
+            Default::default(),
+        )
+        .unwrap();
+        let mut controller =
+            Controller::new(update_controller, ControllerSettings::from_iter(&["test"]));
+        with_world(|world| {
+            let update = |controller: &mut Controller, model: &mut Model| {
+                controller.update(
+                    ROOT,
+                    world,
+                    &mut Info::new(),
+                    &mut model.stateful,
+                    &model.stateless,
+                )
+            };
+            for step in 1..=3 {
+                press(&mut controller, &mut model, Key::Period);
+                update(&mut controller, &mut model);
+                assert!(controller.clock.paused());
+                assert_eq!(model.stateful.step, step);
+                assert!(controller.interpolation().is_some());
+            }
+            // no steps while paused
+            update(&mut controller, &mut model);
+            assert_eq!(model.stateful.step, 3);
+            press(&mut controller, &mut model, Key::R);
+            update(&mut controller, &mut model);
+            assert!(controller.interpolation().is_none());
+        });
+        assert_eq!(bincode::serialize(&model.stateful).unwrap(), initial);
+    }
+
+    #[test]
+    fn reset_replays_simulation() {
+        let Model {
+            stateless,
+            mut stateful,
+        } = model();
+        let initial = bincode::serialize(&stateful).unwrap();
+        let mut controller = UpdateController::new(
+            UpdateSettings::from_iter(&["test"]),
+            &stateless,
+            &stateful,
+            Default::default(),
+        )
+        .unwrap();
+        let update = Command::Update(UpdateArgs { dt: 0.1 });
+        with_world(|world| {
+            let run = |controller: &mut UpdateController, stateful: &mut stateful::Model| {
+                for _ in 0..200 {
+                    controller.execute(ROOT, world, update, stateful, &stateless);
+                }
+                bincode::serialize(stateful).unwrap()
+            };
+            let first = run(&mut controller, &mut stateful);
+            assert_ne!(first, initial);
+            controller.execute(ROOT, world, Command::Reset, &mut stateful, &stateless);
+            assert_eq!(bincode::serialize(&stateful).unwrap(), initial);
+            assert_eq!(run(&mut controller, &mut stateful), first);
+        });
+    }
+}
@@ -1,10 +1,13 @@
-use crate::model::{
-    board::{Board, IntersectionIndex, RoadIndex},
-    common::{
-        AbsoluteDirection, Around, AxisDirection, CarIndex, InOutDirection, LaneDirection,
-        LaneIndex,
+use crate::{
+    model::{
+        board::{Board, IntersectionContext, IntersectionIndex, RoadIndex},
+        common::{
+            AbsoluteDirection, Around, AxisDirection, CarIndex, InOutDirection, LaneDirection,
+            LaneIndex, RelativeDirection,
+        },
+        stateful, stateless,
     },
-    stateful, stateless,
+    util::pool::Pool,
 };
 
 #[derive(Clone, Debug)]
@@ -29,9 +32,12 @@ impl Road {
     }
 }
 
+/// (position, car_index)
+pub type LaneCar = (f64, CarIndex);
+
 #[derive(Default, Clone, Debug)]
 pub struct Lane {
-    pub cars: Vec<(f64, CarIndex)>, // (position, car_index)
+    pub cars: Vec<LaneCar>,
 }
 
 impl Lane {
@@ -39,20 +45,56 @@ impl Lane {
         self.cars
             .sort_by(|(p1, _), (p2, _)| p1.partial_cmp(p2).unwrap());
     }
+
+    /// Return the nearest car behind and the nearest car in front of
+    /// `position`
+    pub fn neighbours(&self, position: f64) -> (Option<LaneCar>, Option<LaneCar>) {
+        let split = self.cars.partition_point(|(p, _)| *p < position);
+        let follower = if split == 0 {
+            None
+        } else {
+            Some(self.cars[split - 1])
+        };
+        let leader = self.cars.get(split).copied();
+        (follower, leader)
+    }
+
+    /// Return the car behind and the car in front of a car on this lane
+    pub fn neighbours_of(&self, car_index: CarIndex) -> (Option<LaneCar>, Option<LaneCar>) {
+        match self.cars.iter().position(|(_, index)| *index == car_index) {
+            Some(i) => (
+                i.checked_sub(1).map(|i| self.cars[i]),
+                self.cars.get(i + 1).copied(),
+            ),
+            None => (None, None),
+        }
+    }
+
+    /// Return the car in front of a car, which may have just moved to this
+    /// lane and is not in its list yet
+    pub fn leader_of(&self, car_index: CarIndex, position: f64) -> Option<LaneCar> {
+        if self.cars.iter().any(|(_, index)| *index == car_index) {
+            self.neighbours_of(car_index).1
+        } else {
+            self.cars.iter().copied().find(|(p, _)| *p > position)
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
 pub struct ProcessLocalState {
-    pub board: Board<(), Option<Road>>,
-    pub car_out_intersection_lane_out_availability: Around<Vec<bool>>,
+    /// Cars on lanes of roads and cars in intersections
+    pub board: Board<Vec<CarIndex>, Option<Road>>,
+    /// Whether out lanes of each source are available for new cars
+    pub source_lane_out_availability: Vec<Around<Vec<bool>>>,
 }
 
 impl ProcessLocalState {
     pub fn empty(
         board: &Board<Option<stateless::Intersection>, Option<stateless::Road>>,
-        car_out_intersection: IntersectionIndex,
+        sources: &[stateless::Source],
     ) -> Self {
-        let mut empty_board = Board::with_shape((), None, board.shape());
+        let mut empty_board = Board::with_shape(Vec::new(), None, board.shape());
         for road_direction in AxisDirection::directions() {
             for (road_index, road) in board.get_roads(*road_direction).enumerate() {
                 if let Some(road) = road {
@@ -65,112 +107,123 @@ impl ProcessLocalState {
                 }
             }
         }
-        let context = board.context_of_intersection(car_out_intersection);
-        let mut car_out_intersection_lane_out_availability: Around<Vec<bool>> = Default::default();
-        for direction in AbsoluteDirection::directions() {
-            if let Some(road_index) = context.get(*direction) {
-                let road = board
-                    .get_road(direction.axis_direction(), *road_index)
-                    .unwrap()
-                    .as_ref()
-                    .unwrap();
-                let lane_direction =
-                    LaneDirection::absolute_in_out_to_lane(*direction, InOutDirection::Out);
-                *car_out_intersection_lane_out_availability.get_mut(*direction) =
-                    vec![true; road.lanes_to_direction(lane_direction).len()];
-            }
-        }
+        let source_lane_out_availability = sources
+            .iter()
+            .map(|source| {
+                let context = board.context_of_intersection(source.intersection);
+                let mut lane_out_availability: Around<Vec<bool>> = Default::default();
+                for direction in AbsoluteDirection::directions() {
+                    if let Some(road_index) = context.get(*direction) {
+                        let road = board
+                            .get_road(direction.axis_direction(), *road_index)
+                            .unwrap()
+                            .as_ref()
+                            .unwrap();
+                        let lane_direction =
+                            LaneDirection::absolute_in_out_to_lane(*direction, InOutDirection::Out);
+                        *lane_out_availability.get_mut(*direction) =
+                            vec![true; road.lanes_to_direction(lane_direction).len()];
+                    }
+                }
+                lane_out_availability
+            })
+            .collect();
         ProcessLocalState {
             board: empty_board,
-            car_out_intersection_lane_out_availability,
+            source_lane_out_availability,
         }
     }
 
     pub fn generate(
         city: &stateless::City,
-        stateful: &[Option<stateful::Car>],
-        _stateless: &[stateless::Car],
+        stateful: &Pool<stateful::Car>,
+        stateless: &[stateless::Car],
     ) -> Self {
-        let mut local_state = Self::empty(&city.board, city.car_out_intersection);
-        let car_out_intersection_context = city
-            .board
-            .context_of_intersection(city.car_out_intersection);
-        for (i, car) in stateful.iter().enumerate() {
-            if let Some(car) = car {
-                match car.location {
-                    stateful::car::Location::OnLane {
+        let mut local_state = Self::empty(&city.board, &city.sources);
+        let source_contexts = city
+            .sources
+            .iter()
+            .map(|source| city.board.context_of_intersection(source.intersection))
+            .collect::<Vec<_>>();
+        for (i, car) in stateful.iter() {
+            match car.location {
+                stateful::car::Location::OnLane {
+                    road_direction,
+                    road_index,
+                    lane_direction,
+                    lane_index,
+                    position,
+                    ..
+                } => {
+                    local_state.insert_car(
                         road_direction,
                         road_index,
                         lane_direction,
                         lane_index,
                         position,
-                        ..
-                    } => {
-                        local_state.insert_car(
-                            road_direction,
-                            road_index,
-                            lane_direction,
-                            lane_index,
-                            position,
-                            i,
-                        );
-                        for direction in AbsoluteDirection::directions() {
-                            if let Some(out_road_index) =
-                                car_out_intersection_context.get(*direction)
-                            {
-                                let out_road_direction = direction.axis_direction();
-                                let out_lane_direction = LaneDirection::absolute_in_out_to_lane(
-                                    *direction,
-                                    InOutDirection::Out,
-                                );
-                                if out_road_direction == road_direction
-                                    && *out_road_index == road_index
-                                    && out_lane_direction == lane_direction
-                                    && position < city.car_out_min_distance
-                                {
-                                    local_state
-                                        .car_out_intersection_lane_out_availability
-                                        .get_mut(*direction)[lane_index] = false;
-                                }
-                            }
-                        }
-                    }
-                    stateful::car::Location::ChangingLane {
+                        i,
+                    );
+                    local_state.update_car_out_availability(
+                        city,
+                        &source_contexts,
+                        road_direction,
+                        road_index,
+                        lane_direction,
+                        lane_index,
+                        position - stateless[car.profile].length,
+                    );
+                }
+                stateful::car::Location::ChangingLane {
+                    road_direction,
+                    road_index,
+                    lane_direction,
+                    from_lane_index,
+                    to_lane_index,
+                    position,
+                    ..
+                } => {
+                    local_state.insert_car(
                         road_direction,
                         road_index,
                         lane_direction,
                         from_lane_index,
+                        position,
+                        i,
+                    );
+                    local_state.insert_car(
+                        road_direction,
+                        road_index,
+                        lane_direction,
                         to_lane_index,
                         position,
-                        ..
-                    } => {
-                        local_state.insert_car(
+                        i,
+                    );
+                    for lane_index in [from_lane_index, to_lane_index].iter() {
+                        local_state.update_car_out_availability(
+                            city,
+                            &source_contexts,
                             road_direction,
                             road_index,
                             lane_direction,
-                            from_lane_index,
-                            position,
-                            i,
+                            *lane_index,
+                            position - stateless[car.profile].length,
                         );
-                        local_state.insert_car(
-                            road_direction,
-                            road_index,
-                            lane_direction,
-                            to_lane_index,
-                            position,
-                            i,
-                        )
                     }
-                    stateful::car::Location::InIntersection {
-                        intersection_index,
-                        to_direction,
-                        to_lane_index,
-                        ..
-                    } => {
-                        if intersection_index == city.car_out_intersection {
-                            local_state
-                                .car_out_intersection_lane_out_availability
-                                .get_mut(to_direction)[to_lane_index] = false;
+                }
+                stateful::car::Location::InIntersection {
+                    intersection_index,
+                    to_direction,
+                    to_lane_index,
+                    ..
+                } => {
+                    local_state.board.intersections[intersection_index].push(i);
+                    for (source, availability) in city
+                        .sources
+                        .iter()
+                        .zip(local_state.source_lane_out_availability.iter_mut())
+                    {
+                        if source.intersection == intersection_index {
+                            availability.get_mut(to_direction)[to_lane_index] = false;
                         }
                     }
                 }
@@ -180,6 +233,42 @@ impl ProcessLocalState {
         local_state
     }
 
+    /// Mark the lane as unavailable for new cars if the rear of a car is too
+    /// close to a source
+    #[allow(clippy::too_many_arguments)]
+    fn update_car_out_availability(
+        &mut self,
+        city: &stateless::City,
+        source_contexts: &[IntersectionContext],
+        road_direction: AxisDirection,
+        road_index: RoadIndex,
+        lane_direction: LaneDirection,
+        lane_index: LaneIndex,
+        rear_position: f64,
+    ) {
+        if rear_position >= city.car_out_min_distance {
+            return;
+        }
+        for (context, availability) in source_contexts
+            .iter()
+            .zip(self.source_lane_out_availability.iter_mut())
+        {
+            for direction in AbsoluteDirection::directions() {
+                if let Some(out_road_index) = context.get(*direction) {
+                    let out_road_direction = direction.axis_direction();
+                    let out_lane_direction =
+                        LaneDirection::absolute_in_out_to_lane(*direction, InOutDirection::Out);
+                    if out_road_direction == road_direction
+                        && *out_road_index == road_index
+                        && out_lane_direction == lane_direction
+                    {
+                        availability.get_mut(*direction)[lane_index] = false;
+                    }
+                }
+            }
+        }
+    }
+
     pub fn insert_car(
         &mut self,
         road_direction: AxisDirection,
@@ -199,6 +288,158 @@ impl ProcessLocalState {
             .push((position, car_index));
     }
 
+    pub fn lane(
+        &self,
+        road_direction: AxisDirection,
+        road_index: RoadIndex,
+        lane_direction: LaneDirection,
+        lane_index: LaneIndex,
+    ) -> &Lane {
+        &self.board.get_roads(road_direction)[road_index]
+            .as_ref()
+            .unwrap()
+            .lanes_to_direction(lane_direction)[lane_index]
+    }
+
+    /// Cars in an intersection
+    pub fn intersection_cars(&self, intersection_index: IntersectionIndex) -> &[CarIndex] {
+        &self.board.intersections[intersection_index]
+    }
+
+    /// Choose the out lane to `to_direction` of an intersection with the most
+    /// free space, the lowest index is chosen on a tie.
+    ///
+    /// The free space of a lane is the position of the rear of its last car,
+    /// minus the length of cars in the intersection heading to the lane.
+    pub fn out_lane(
+        &self,
+        stateless: &stateless::Model,
+        stateful: &Pool<stateful::Car>,
+        intersection_index: IntersectionIndex,
+        to_direction: AbsoluteDirection,
+    ) -> Option<LaneIndex> {
+        let road_index = (*stateless
+            .city
+            .board
+            .context_of_intersection(intersection_index)
+            .get(to_direction))?;
+        let road_direction = to_direction.axis_direction();
+        let lane_direction =
+            LaneDirection::absolute_in_out_to_lane(to_direction, InOutDirection::Out);
+        let lanes = self.board.get_roads(road_direction)[road_index]
+            .as_ref()?
+            .lanes_to_direction(lane_direction);
+        let mut best: Option<(f64, LaneIndex)> = None;
+        for (lane_index, lane) in lanes.iter().enumerate() {
+            let rear = lane.cars.first().map_or(
+                stateless.city.road_length(road_direction, road_index),
+                |(p, i)| p - stateless.car(&stateful[*i]).length,
+            );
+            let entering: f64 = self
+                .intersection_cars(intersection_index)
+                .iter()
+                .filter(|&&i| {
+                    matches!(
+                        stateful[i].location,
+                        stateful::car::Location::InIntersection {
+                            to_direction: d,
+                            to_lane_index: l,
+                            ..
+                        } if d == to_direction && l == lane_index
+                    )
+                })
+                .map(|&i| stateless.car(&stateful[i]).length)
+                .sum();
+            let free_space = rear - entering;
+            let better = match best {
+                Some((best_free_space, _)) => free_space > best_free_space,
+                None => true,
+            };
+            if better {
+                best = Some((free_space, lane_index));
+            }
+        }
+        best.map(|(_, lane_index)| lane_index)
+    }
+
+    /// Find the leader of a car at the end of a lane along its planned path,
+    /// through the intersection and into the out lane chosen by `out_lane`.
+    ///
+    /// Return the distance from the end of the lane to the rear of the leader.
+    pub fn path_leader(
+        &self,
+        stateless: &stateless::Model,
+        stateful: &Pool<stateful::Car>,
+        (road_direction, road_index, lane_direction): (AxisDirection, RoadIndex, LaneDirection),
+        lane_index: LaneIndex,
+        about_to_turn: RelativeDirection,
+    ) -> Option<LaneCar> {
+        let intersection_index = stateless.city.board.lane_to_intersection_index(
+            road_direction,
+            road_index,
+            lane_direction,
+        );
+        let driver_direction = AbsoluteDirection::of_lane(road_direction, lane_direction);
+        let from_direction = driver_direction.turn_back();
+        let to_direction = driver_direction.turn(about_to_turn);
+        let to_lane_index = self.out_lane(stateless, stateful, intersection_index, to_direction)?;
+        let path_length = stateless.city.intersection_path_total_length(
+            intersection_index,
+            from_direction,
+            lane_index,
+            to_direction,
+            to_lane_index,
+        )?;
+
+        // cars in the intersection from the same lane, measured along their
+        // own path from the shared start, or from other lanes to the same
+        // lane, projected back from the shared end onto this path
+        let in_intersection = self
+            .intersection_cars(intersection_index)
+            .iter()
+            .filter_map(|&i| match stateful[i].location {
+                stateful::car::Location::InIntersection {
+                    from_direction: d,
+                    from_lane_index: l,
+                    to_direction: to_d,
+                    to_lane_index: to_l,
+                    total_length,
+                    position,
+                    ..
+                } => {
+                    let length = stateless.car(&stateful[i]).length;
+                    if d == from_direction && l == lane_index {
+                        Some((position - length, i))
+                    } else if to_d == to_direction && to_l == to_lane_index {
+                        // cars early on longer paths are at the start of this path
+                        let rear = path_length - (total_length - position) - length;
+                        Some((rear.max(0.0), i))
+                    } else {
+                        None
+                    }
+                }
+                _ => None,
+            });
+        let out_road_index = (*stateless
+            .city
+            .board
+            .context_of_intersection(intersection_index)
+            .get(to_direction))?;
+        let out_lane = self.lane(
+            to_direction.axis_direction(),
+            out_road_index,
+            LaneDirection::absolute_in_out_to_lane(to_direction, InOutDirection::Out),
+            to_lane_index,
+        );
+        let on_out_lane = out_lane
+            .cars
+            .first()
+            .map(|&(p, i)| (path_length + p - stateless.car(&stateful[i]).length, i));
+        in_intersection
+            .chain(on_out_lane)
+            .min_by(|(d1, _), (d2, _)| d1.partial_cmp(d2).unwrap())
+    }
+
     pub fn sort_all(&mut self) {
         for road_direction in AxisDirection::directions() {
             for road in self
@@ -216,3 +457,131 @@ impl ProcessLocalState {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lane_neighbours() {
+        let lane = Lane {
+            cars: vec![(1.0, 3), (5.0, 0), (9.0, 2)],
+        };
+        assert_eq!(lane.neighbours(0.0), (None, Some((1.0, 3))));
+        assert_eq!(lane.neighbours(5.0), (Some((1.0, 3)), Some((5.0, 0))));
+        assert_eq!(lane.neighbours(6.0), (Some((5.0, 0)), Some((9.0, 2))));
+        assert_eq!(lane.neighbours(10.0), (Some((9.0, 2)), None));
+        assert_eq!(Lane::default().neighbours(1.0), (None, None));
+    }
+
+    #[test]
+    fn lane_neighbours_of() {
+        let lane = Lane {
+            cars: vec![(1.0, 3), (5.0, 0), (9.0, 2)],
+        };
+        assert_eq!(lane.neighbours_of(3), (None, Some((5.0, 0))));
+        assert_eq!(lane.neighbours_of(0), (Some((1.0, 3)), Some((9.0, 2))));
+        assert_eq!(lane.neighbours_of(2), (Some((5.0, 0)), None));
+        assert_eq!(lane.neighbours_of(1), (None, None));
+    }
+
+    /// Find an intersection path from the lane 0 of `from` to the single out
+    /// lane of `to`, and another path from the lane 0 of `other` to it
+    fn merging_paths(
+        model: &stateless::Model,
+    ) -> Option<(
+        IntersectionIndex,
+        AbsoluteDirection,
+        AbsoluteDirection,
+        AbsoluteDirection,
+    )> {
+        let board = &model.city.board;
+        let lanes = |index, direction: AbsoluteDirection, in_out| {
+            let road_index = (*board.context_of_intersection(index).get(direction))?;
+            let road = board.get_roads(direction.axis_direction())[road_index].as_ref()?;
+            let lane_direction = LaneDirection::absolute_in_out_to_lane(direction, in_out);
+            Some(road.lanes_to_direction(lane_direction).len())
+        };
+        for (index, _) in board.intersections.enumerate() {
+            for &to in AbsoluteDirection::directions() {
+                if lanes(index, to, InOutDirection::Out) != Some(1) {
+                    continue;
+                }
+                let from_directions = AbsoluteDirection::directions()
+                    .copied()
+                    .filter(|&from| {
+                        from != to
+                            && lanes(index, from, InOutDirection::In).unwrap_or(0) > 0
+                            && model
+                                .city
+                                .intersection_path_total_length(index, from, 0, to, 0)
+                                .is_some()
+                    })
+                    .collect::<Vec<_>>();
+                if let [from, other, ..] = from_directions[..] {
+                    return Some((index, from, other, to));
+                }
+            }
+        }
+        None
+    }
+
+    #[test]
+    fn path_leader_from_other_lane() {
+        use crate::model::generate::stateless::{
+            generate_stateless_model, StatelessModelGenerationSettings,
+        };
+        use std::collections::VecDeque;
+        use structopt::StructOpt;
+
+        let model = generate_stateless_model(StatelessModelGenerationSettings::from_iter(&[
+            "test", "--seed", "2",
+        ]))
+        .unwrap();
+        let (index, from, other, to) = merging_paths(&model).unwrap();
+        let city = &model.city;
+        let path_length = city
+            .intersection_path_total_length(index, from, 0, to, 0)
+            .unwrap();
+        let total_length = city
+            .intersection_path_total_length(index, other, 0, to, 0)
+            .unwrap();
+        let length = model.cars[0].length;
+        let road_index = (*city.board.context_of_intersection(index).get(from)).unwrap();
+        let lane = (
+            from.axis_direction(),
+            road_index,
+            LaneDirection::absolute_in_out_to_lane(from, InOutDirection::In),
+        );
+        let about_to_turn = from.turn_back().should_turn(to);
+        for position in [total_length - 0.5, 0.0].iter() {
+            let mut cars = Pool::new();
+            let id = cars.insert(stateful::Car {
+                location: stateful::car::Location::InIntersection {
+                    intersection_index: index,
+                    from_direction: other,
+                    from_lane_index: 0,
+                    to_direction: to,
+                    to_lane_index: 0,
+                    total_length,
+                    position: *position,
+                },
+                velocity: 0.0,
+                acceleration: 0.0,
+                trip: stateful::car::Trip {
+                    origin: index,
+                    destination: index,
+                    route: VecDeque::new(),
+                },
+                profile: 0,
+            });
+            let local_state = ProcessLocalState::generate(city, &cars, &model.cars);
+            let (distance, leader) = local_state
+                .path_leader(&model, &cars, lane, 0, about_to_turn)
+                .unwrap();
+            assert_eq!(leader, id);
+            let expected = (path_length - (total_length - position) - length).max(0.0);
+            assert!((distance - expected).abs() < 1e-9);
+        }
+    }
+}
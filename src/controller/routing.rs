@@ -0,0 +1,418 @@
+//! Shortest path routing over the road graph
+//!
+//! A node of the graph is a road to a lane direction, an edge is a turn at
+//! the intersection at the end of the road allowed by the direction rules of
+//! its lanes. A route ends at the road leading to the destination.
+
+use crate::model::{
+    board::{IntersectionIndex, RoadIndex},
+    common::{
+        AbsoluteDirection, AxisDirection, InOutDirection, LaneDirection, RelativeDirection,
+        TurnRule,
+    },
+    stateless,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, BinaryHeap, VecDeque},
+};
+
+/// A road to a lane direction
+pub type RoadState = (AxisDirection, RoadIndex, LaneDirection);
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Route {
+    /// Turns at the intersections at the end of roads on the route
+    pub turns: VecDeque<RelativeDirection>,
+    /// Cost of roads on the route after the start road
+    pub cost: f64,
+}
+
+/// Roads reachable by turning at the end of `road`
+pub fn next_roads(
+    city: &stateless::City,
+    (road_direction, road_index, lane_direction): RoadState,
+) -> Vec<(RelativeDirection, RoadState)> {
+    let road = match city.board.get_road(road_direction, road_index) {
+        Some(Some(road)) => road,
+        _ => return Vec::new(),
+    };
+    let turn_rule = road.turn_rule_to_direction(lane_direction);
+    let intersection_index =
+        city.board
+            .lane_to_intersection_index(road_direction, road_index, lane_direction);
+    let context = city.board.context_of_intersection(intersection_index);
+    let driver_direction = AbsoluteDirection::of_lane(road_direction, lane_direction);
+    RelativeDirection::directions()
+        .filter(|turn| turn_rule.contains(turn.to_turn_rule()))
+        .filter_map(|&turn| {
+            let to_direction = driver_direction.turn(turn);
+            let to_road_index = (*context.get(to_direction))?;
+            let to_lane_direction =
+                LaneDirection::absolute_in_out_to_lane(to_direction, InOutDirection::Out);
+            let to_road = city
+                .board
+                .get_road(to_direction.axis_direction(), to_road_index)?
+                .as_ref()?;
+            if to_road.lanes_to_direction(to_lane_direction).is_empty() {
+                None
+            } else {
+                Some((
+                    turn,
+                    (
+                        to_direction.axis_direction(),
+                        to_road_index,
+                        to_lane_direction,
+                    ),
+                ))
+            }
+        })
+        .collect()
+}
+
+/// Find the shortest route from `from` to `destination` by road length
+pub fn shortest_route(
+    city: &stateless::City,
+    from: RoadState,
+    destination: IntersectionIndex,
+) -> Option<Route> {
+    shortest_route_by(
+        city,
+        from,
+        destination,
+        |(road_direction, road_index, _)| city.road_length(road_direction, road_index),
+    )
+}
+
+/// Find the shortest route from `road` to `destination` starting with a turn
+/// in `turn_rule`, return the turn and the route after it
+pub fn shortest_route_turning(
+    city: &stateless::City,
+    road: RoadState,
+    turn_rule: TurnRule,
+    destination: IntersectionIndex,
+) -> Option<(RelativeDirection, Route)> {
+    next_roads(city, road)
+        .into_iter()
+        .filter(|(turn, _)| turn_rule.contains(turn.to_turn_rule()))
+        .filter_map(|(turn, next_road @ (road_direction, road_index, _))| {
+            let route = shortest_route(city, next_road, destination)?;
+            let cost = route.cost + city.road_length(road_direction, road_index);
+            Some((
+                turn,
+                Route {
+                    turns: route.turns,
+                    cost,
+                },
+            ))
+        })
+        .min_by(|(_, a), (_, b)| a.cost.partial_cmp(&b.cost).unwrap())
+}
+
+/// Find the route with the lowest total cost of roads with Dijkstra's
+/// algorithm, ties are broken by the order of roads so the result is
+/// deterministic.
+pub fn shortest_route_by<F>(
+    city: &stateless::City,
+    from: RoadState,
+    destination: IntersectionIndex,
+    road_cost: F,
+) -> Option<Route>
+where
+    F: Fn(RoadState) -> f64,
+{
+    let mut costs = BTreeMap::new();
+    let mut previous: BTreeMap<RoadState, (RoadState, RelativeDirection)> = BTreeMap::new();
+    let mut heap = BinaryHeap::new();
+    costs.insert(from, 0.0);
+    heap.push(Candidate {
+        cost: 0.0,
+        road: from,
+    });
+    while let Some(Candidate { cost, road }) = heap.pop() {
+        if cost > costs[&road] {
+            continue;
+        }
+        let (road_direction, road_index, lane_direction) = road;
+        if city
+            .board
+            .lane_to_intersection_index(road_direction, road_index, lane_direction)
+            == destination
+        {
+            let mut turns = VecDeque::new();
+            let mut current = road;
+            while let Some(&(previous_road, turn)) = previous.get(&current) {
+                turns.push_front(turn);
+                current = previous_road;
+            }
+            return Some(Route { turns, cost });
+        }
+        for (turn, next_road) in next_roads(city, road) {
+            let next_cost = cost + road_cost(next_road);
+            let better = match costs.get(&next_road) {
+                Some(&known_cost) => next_cost < known_cost,
+                None => true,
+            };
+            if better {
+                costs.insert(next_road, next_cost);
+                previous.insert(next_road, (road, turn));
+                heap.push(Candidate {
+                    cost: next_cost,
+                    road: next_road,
+                });
+            }
+        }
+    }
+    None
+}
+
+/// Velocities of cars summed over each road to a lane direction of the whole
+/// city, to estimate travel times for routing.
+///
+/// Velocities are summed in millimeters per second as integers, so sums over
+/// the cars of all processes do not depend on the number of processes.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct RoadVelocities {
+    pub sums: Vec<u64>,
+    pub counts: Vec<u64>,
+}
+
+impl RoadVelocities {
+    pub fn new(city: &stateless::City) -> Self {
+        let board = &city.board;
+        let roads = (board.horizontal_roads.storage.len() + board.vertical_roads.storage.len()) * 2;
+        RoadVelocities {
+            sums: vec![0; roads],
+            counts: vec![0; roads],
+        }
+    }
+
+    fn offset(
+        city: &stateless::City,
+        (road_direction, road_index, lane_direction): RoadState,
+    ) -> usize {
+        let board = &city.board;
+        let road = match road_direction {
+            AxisDirection::Horizontal => board.horizontal_roads.offset_unchecked(road_index),
+            AxisDirection::Vertical => {
+                board.horizontal_roads.storage.len()
+                    + board.vertical_roads.offset_unchecked(road_index)
+            }
+        };
+        road * 2
+            + match lane_direction {
+                LaneDirection::LowToHigh => 0,
+                LaneDirection::HighToLow => 1,
+            }
+    }
+
+    pub fn add(&mut self, city: &stateless::City, road: RoadState, velocity: f64) {
+        let offset = Self::offset(city, road);
+        self.sums[offset] += (velocity.max(0.0) * 1000.0).round() as u64;
+        self.counts[offset] += 1;
+    }
+
+    /// Travel time of a road estimated from the mean velocity of cars on its
+    /// lanes, or from the max speed of the lanes if there is no car.
+    pub fn travel_time(&self, city: &stateless::City, road: RoadState) -> f64 {
+        let (road_direction, road_index, lane_direction) = road;
+        let offset = Self::offset(city, road);
+        let velocity = if self.counts[offset] == 0 {
+            city.board.get_roads(road_direction)[road_index]
+                .as_ref()
+                .unwrap()
+                .lanes_to_direction(lane_direction)
+                .iter()
+                .map(|lane| lane.max_speed)
+                .fold(0.0, f64::max)
+        } else {
+            self.sums[offset] as f64 / 1000.0 / self.counts[offset] as f64
+        };
+        // stopped cars still leave the road eventually
+        city.road_length(road_direction, road_index) / velocity.max(1.0)
+    }
+}
+
+/// Entry of the priority queue, ordered reversely by cost
+struct Candidate {
+    cost: f64,
+    road: RoadState,
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .cost
+            .partial_cmp(&self.cost)
+            .unwrap()
+            .then_with(|| other.road.cmp(&self.road))
+    }
+}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Candidate {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{
+        board::Board,
+        common::TurnRule,
+        stateless::{City, Intersection, Lane, Road},
+    };
+
+    /// Three intersections in a row connected by two roads
+    fn row_city(second_road: Road) -> City {
+        let lane = Lane {
+            max_speed: 40.0,
+            direction_rule: TurnRule::ALL,
+        };
+        let mut board = Board::with_shape(None, None, (1, 3));
+        board.intersections[(0, 0)] = Some(Intersection::End { max_speed: 10.0 });
+        board.intersections[(0, 1)] = Some(Intersection::Straight);
+        board.intersections[(0, 2)] = Some(Intersection::End { max_speed: 10.0 });
+        board.horizontal_roads[(0, 0)] = Some(Road {
+            lane_to_high: vec![lane.clone()],
+            lane_to_low: vec![lane],
+        });
+        board.horizontal_roads[(0, 1)] = Some(second_road);
+        City {
+            board,
+            sources: vec![],
+            sinks: vec![(0, 0), (0, 2)],
+            car_out_min_distance: 8.0,
+            lane_width: 3.5,
+            horizontal_road_length: vec![100.0, 100.0],
+            vertical_road_length: vec![],
+            intersection_height: vec![20.0],
+            intersection_width: vec![20.0, 20.0, 20.0],
+        }
+    }
+
+    #[test]
+    fn route_through_intersection() {
+        let lane = Lane {
+            max_speed: 40.0,
+            direction_rule: TurnRule::ALL,
+        };
+        let city = row_city(Road {
+            lane_to_high: vec![lane.clone()],
+            lane_to_low: vec![lane],
+        });
+        let from = (AxisDirection::Horizontal, (0, 0), LaneDirection::LowToHigh);
+        let route = shortest_route(&city, from, (0, 2)).unwrap();
+        assert_eq!(route.turns, vec![RelativeDirection::Front]);
+        assert!((route.cost - 100.0).abs() < 1e-9);
+        // the destination is at the end of the start road
+        let route = shortest_route(&city, from, (0, 1)).unwrap();
+        assert!(route.turns.is_empty());
+    }
+
+    #[test]
+    fn route_avoids_slow_road() {
+        let lane = Lane {
+            max_speed: 40.0,
+            direction_rule: TurnRule::ALL,
+        };
+        let road = Road {
+            lane_to_high: vec![lane.clone()],
+            lane_to_low: vec![lane],
+        };
+        // four intersections in a square
+        let board = Board::with_shape(
+            Some(Intersection::Turn { max_speed: 10.0 }),
+            Some(road),
+            (2, 2),
+        );
+        let city = City {
+            board,
+            horizontal_road_length: vec![100.0],
+            vertical_road_length: vec![100.0],
+            intersection_height: vec![20.0, 20.0],
+            intersection_width: vec![20.0, 20.0],
+            ..row_city(Road {
+                lane_to_high: vec![],
+                lane_to_low: vec![],
+            })
+        };
+        let from = (AxisDirection::Horizontal, (0, 0), LaneDirection::LowToHigh);
+        let slow_road = (AxisDirection::Vertical, (0, 1), LaneDirection::LowToHigh);
+        let route = shortest_route_by(&city, from, (1, 0), |road| {
+            if road == slow_road {
+                1000.0
+            } else {
+                100.0
+            }
+        })
+        .unwrap();
+        use RelativeDirection::*;
+        assert_eq!(route.turns, vec![Back, Left]);
+        let slow_road = (AxisDirection::Horizontal, (0, 0), LaneDirection::HighToLow);
+        let route = shortest_route_by(&city, from, (1, 0), |road| {
+            if road == slow_road {
+                1000.0
+            } else {
+                100.0
+            }
+        })
+        .unwrap();
+        assert_eq!(route.turns, vec![Right, Right]);
+    }
+
+    #[test]
+    fn travel_time_of_road_velocities() {
+        let lane = Lane {
+            max_speed: 40.0,
+            direction_rule: TurnRule::ALL,
+        };
+        let city = row_city(Road {
+            lane_to_high: vec![lane.clone()],
+            lane_to_low: vec![lane],
+        });
+        let road = (AxisDirection::Horizontal, (0, 1), LaneDirection::LowToHigh);
+        let velocities = [10.0, 20.0, 0.1234];
+        let mut all = RoadVelocities::new(&city);
+        velocities.iter().for_each(|v| all.add(&city, road, *v));
+        // sums of cars split between processes, in another order
+        let mut first = RoadVelocities::new(&city);
+        let mut second = RoadVelocities::new(&city);
+        first.add(&city, road, velocities[2]);
+        second.add(&city, road, velocities[1]);
+        second.add(&city, road, velocities[0]);
+        for (i, (sum, count)) in all.sums.iter().zip(all.counts.iter()).enumerate() {
+            assert_eq!(*sum, first.sums[i] + second.sums[i]);
+            assert_eq!(*count, first.counts[i] + second.counts[i]);
+        }
+        let mean = (10.0 + 20.0 + 0.123) / 3.0;
+        assert!((all.travel_time(&city, road) - 100.0 / mean).abs() < 1e-9);
+        // an empty road is driven at the max speed of its lanes
+        let empty = (AxisDirection::Horizontal, (0, 0), LaneDirection::HighToLow);
+        assert!((all.travel_time(&city, empty) - 100.0 / 40.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn route_respects_one_way_road() {
+        let lane = Lane {
+            max_speed: 40.0,
+            direction_rule: TurnRule::ALL,
+        };
+        let city = row_city(Road {
+            lane_to_high: vec![],
+            lane_to_low: vec![lane],
+        });
+        let from = (AxisDirection::Horizontal, (0, 0), LaneDirection::LowToHigh);
+        assert_eq!(shortest_route(&city, from, (0, 2)), None);
+    }
+}
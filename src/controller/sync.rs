@@ -0,0 +1,395 @@
+//! Incremental synchronization of the stateful model
+//!
+//! Processes send changes since the last step instead of whole states.
+//! Motions of cars and signals of intersections use fixed-size encodings.
+
+use crate::{
+    communication::Traffic,
+    model::{
+        board::IntersectionIndex,
+        common::{
+            AbsoluteDirection, Around, AxisDirection, LaneDirection, RelativeDirection, TurnRule,
+        },
+        stateful::{self, car::Location, Car},
+    },
+    util::pool::Pool,
+};
+use serde::{Deserialize, Serialize};
+use std::slice::Iter;
+
+/// Bytes exchanged by this process in a step of cars
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct StepTraffic {
+    /// Changes of signals to the root and of sources from the root, since
+    /// the last step of cars
+    pub city: Traffic,
+    pub halo: Traffic,
+    pub migration: Traffic,
+    /// Changes of cars to the root for the view
+    pub view: Traffic,
+}
+
+impl StepTraffic {
+    pub fn total(&self) -> Traffic {
+        let mut total = self.city;
+        total += self.halo;
+        total += self.migration;
+        total += self.view;
+        total
+    }
+}
+
+/// Changes of a city sent by the root.
+///
+/// Only waiting cars of sources are synchronized, other states of sources
+/// are kept by the root.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct CityDelta {
+    time: f64,
+    waiting: Vec<(u32, u32)>,
+}
+
+impl CityDelta {
+    pub fn between(old: &[stateful::Source], new: &stateful::City) -> Self {
+        let waiting = new
+            .sources
+            .iter()
+            .zip(old.iter())
+            .enumerate()
+            .filter(|(_, (new, old))| new.waiting != old.waiting)
+            .map(|(index, (new, _))| (index as u32, new.waiting as u32))
+            .collect();
+        CityDelta {
+            time: new.time,
+            waiting,
+        }
+    }
+
+    pub fn apply(&self, city: &mut stateful::City) {
+        city.time = self.time;
+        for (index, waiting) in self.waiting.iter() {
+            city.sources[*index as usize].waiting = *waiting as usize;
+        }
+    }
+}
+
+/// A changed signal of an intersection
+pub type SignalChange = ((u32, u32), Around<TurnRule>);
+
+pub fn signal_change(index: IntersectionIndex, current: Around<TurnRule>) -> SignalChange {
+    ((index.0 as u32, index.1 as u32), current)
+}
+
+pub fn apply_signal_changes(city: &mut stateful::City, changes: &[SignalChange]) {
+    for ((i, j), signal) in changes.iter() {
+        let index: IntersectionIndex = (*i as usize, *j as usize);
+        if let Some(current) = city.board.intersections[index]
+            .as_mut()
+            .and_then(stateful::Intersection::current_mut)
+        {
+            *current = *signal;
+        }
+    }
+}
+
+/// Change of a car owned by a process
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum CarDelta {
+    /// A car new to the process, or with a changed trip
+    Full(u32, Car),
+    /// Motion of a car owned by the process in the last step
+    Motion(u32, Motion),
+    /// A car removed or moved to another process
+    Removed(u32),
+}
+
+/// Changes of the cars owned by a process since the last step
+pub fn car_deltas(previous: &Pool<Car>, current: &Pool<Car>) -> Vec<CarDelta> {
+    let removed = previous
+        .ids()
+        .filter(|car_index| !current.contains(*car_index))
+        .map(|car_index| CarDelta::Removed(car_index as u32));
+    let changed = current
+        .iter()
+        .map(|(car_index, car)| match previous.get(car_index) {
+            Some(old) if old.trip == car.trip && old.profile == car.profile => {
+                CarDelta::Motion(car_index as u32, Motion::of(car))
+            }
+            _ => CarDelta::Full(car_index as u32, car.clone()),
+        });
+    removed.chain(changed).collect()
+}
+
+/// Apply changes of cars of all other processes to `cars`
+pub fn apply_car_deltas(cars: &mut Pool<Car>, deltas: Vec<CarDelta>) {
+    // removals first, a car moved between processes is removed by the old
+    // owner and added by the new one
+    for delta in deltas.iter() {
+        if let CarDelta::Removed(car_index) = delta {
+            cars.remove(*car_index as usize);
+        }
+    }
+    let next_id = cars.next_id();
+    let mut entries = std::mem::take(cars).into_entries();
+    let mut added = Vec::new();
+    for delta in deltas {
+        match delta {
+            CarDelta::Full(car_index, car) => added.push((car_index as usize, car)),
+            CarDelta::Motion(car_index, motion) => {
+                if let Ok(position) =
+                    entries.binary_search_by_key(&(car_index as usize), |(id, _)| *id)
+                {
+                    motion.apply(&mut entries[position].1);
+                }
+            }
+            CarDelta::Removed(_) => (),
+        }
+    }
+    added.sort_by_key(|(car_index, _)| *car_index);
+    // cars spawned by other processes may have IDs unknown to this view
+    let next_id = match added.last() {
+        Some((car_index, _)) => next_id.max(car_index + 1),
+        None => next_id,
+    };
+    entries.retain(|(car_index, _)| {
+        added
+            .binary_search_by_key(car_index, |(id, _)| *id)
+            .is_err()
+    });
+    entries.extend(added);
+    *cars = Pool::from_entries(entries, next_id);
+}
+
+/// Fixed-size encoding of the location, velocity and acceleration of a car
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct Motion {
+    /// 0 on a lane, 1 changing lane, 2 in an intersection
+    kind: u8,
+    /// Index of the road or the intersection
+    index: (u32, u32),
+    /// Axis and lane direction of the road, or from and to directions in the
+    /// intersection
+    directions: (u8, u8),
+    /// Lane index, or from and to lane indices
+    lanes: (u8, u8),
+    about_to_turn: u8,
+    position: f64,
+    /// Lane changed proportion, or total length of the path in the
+    /// intersection
+    extra: f64,
+    velocity: f64,
+    acceleration: f64,
+}
+
+fn encode<T: PartialEq + 'static>(mut all: Iter<'static, T>, value: &T) -> u8 {
+    all.position(|v| v == value).unwrap() as u8
+}
+
+fn decode<T: Copy + 'static>(mut all: Iter<'static, T>, code: u8) -> T {
+    *all.nth(code as usize).unwrap()
+}
+
+impl Motion {
+    pub fn of(car: &Car) -> Self {
+        let to_u32 = |(i, j): (usize, usize)| (i as u32, j as u32);
+        let (kind, index, directions, lanes, about_to_turn, position, extra) = match &car.location {
+            Location::OnLane {
+                road_direction,
+                road_index,
+                lane_direction,
+                lane_index,
+                about_to_turn,
+                position,
+            } => (
+                0,
+                to_u32(*road_index),
+                (
+                    encode(AxisDirection::directions(), road_direction),
+                    encode(LaneDirection::directions(), lane_direction),
+                ),
+                (*lane_index as u8, 0),
+                encode(RelativeDirection::directions(), about_to_turn),
+                *position,
+                0.0,
+            ),
+            Location::ChangingLane {
+                road_direction,
+                road_index,
+                lane_direction,
+                from_lane_index,
+                to_lane_index,
+                about_to_turn,
+                position,
+                lane_changed_proportion,
+            } => (
+                1,
+                to_u32(*road_index),
+                (
+                    encode(AxisDirection::directions(), road_direction),
+                    encode(LaneDirection::directions(), lane_direction),
+                ),
+                (*from_lane_index as u8, *to_lane_index as u8),
+                encode(RelativeDirection::directions(), about_to_turn),
+                *position,
+                *lane_changed_proportion,
+            ),
+            Location::InIntersection {
+                intersection_index,
+                from_direction,
+                from_lane_index,
+                to_direction,
+                to_lane_index,
+                total_length,
+                position,
+            } => (
+                2,
+                to_u32(*intersection_index),
+                (
+                    encode(AbsoluteDirection::directions(), from_direction),
+                    encode(AbsoluteDirection::directions(), to_direction),
+                ),
+                (*from_lane_index as u8, *to_lane_index as u8),
+                0,
+                *position,
+                *total_length,
+            ),
+        };
+        Motion {
+            kind,
+            index,
+            directions,
+            lanes,
+            about_to_turn,
+            position,
+            extra,
+            velocity: car.velocity,
+            acceleration: car.acceleration,
+        }
+    }
+
+    pub fn apply(&self, car: &mut Car) {
+        let index = (self.index.0 as usize, self.index.1 as usize);
+        let (first, second) = self.directions;
+        let (lane, other_lane) = (self.lanes.0 as usize, self.lanes.1 as usize);
+        let position = self.position;
+        let extra = self.extra;
+        car.location = match self.kind {
+            0 => Location::OnLane {
+                road_direction: decode(AxisDirection::directions(), first),
+                road_index: index,
+                lane_direction: decode(LaneDirection::directions(), second),
+                lane_index: lane,
+                about_to_turn: decode(RelativeDirection::directions(), self.about_to_turn),
+                position,
+            },
+            1 => Location::ChangingLane {
+                road_direction: decode(AxisDirection::directions(), first),
+                road_index: index,
+                lane_direction: decode(LaneDirection::directions(), second),
+                from_lane_index: lane,
+                to_lane_index: other_lane,
+                about_to_turn: decode(RelativeDirection::directions(), self.about_to_turn),
+                position,
+                lane_changed_proportion: extra,
+            },
+            2 => Location::InIntersection {
+                intersection_index: index,
+                from_direction: decode(AbsoluteDirection::directions(), first),
+                from_lane_index: lane,
+                to_direction: decode(AbsoluteDirection::directions(), second),
+                to_lane_index: other_lane,
+                total_length: extra,
+                position,
+            },
+            _ => unreachable!(),
+        };
+        car.velocity = self.velocity;
+        car.acceleration = self.acceleration;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::stateful::car::Trip;
+    use std::collections::VecDeque;
+
+    fn car(position: f64) -> Car {
+        Car {
+            location: Location::ChangingLane {
+                road_direction: AxisDirection::Vertical,
+                road_index: (2, 3),
+                lane_direction: LaneDirection::HighToLow,
+                from_lane_index: 1,
+                to_lane_index: 0,
+                about_to_turn: RelativeDirection::Left,
+                position,
+                lane_changed_proportion: 0.5,
+            },
+            velocity: 10.0,
+            acceleration: -1.0,
+            trip: Trip {
+                origin: (0, 0),
+                destination: (3, 3),
+                route: VecDeque::new(),
+            },
+            profile: 4,
+        }
+    }
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn motion_round_trip() {
+        let mut decoded = car(0.0);
+        // not representable in single precision
+        let position = 12.345_678_901_234;
+        let mut moving = car(position);
+        moving.velocity = 0.1;
+        Motion::of(&moving).apply(&mut decoded);
+        assert!(matches!(
+            decoded.location,
+            Location::ChangingLane {
+                road_direction: AxisDirection::Vertical,
+                road_index: (2, 3),
+                lane_direction: LaneDirection::HighToLow,
+                from_lane_index: 1,
+                to_lane_index: 0,
+                about_to_turn: RelativeDirection::Left,
+                ..
+            }
+        ));
+        assert_eq!(decoded.location.lane_position(), Some(position));
+        assert_eq!(decoded.velocity, 0.1);
+        assert_eq!(
+            bincode::serialized_size(&Motion::of(&decoded)).unwrap(),
+            bincode::serialized_size(&Motion::of(&car(0.0))).unwrap()
+        );
+    }
+
+    #[test]
+    fn apply_deltas() {
+        let previous = Pool::from_entries(vec![(0, car(1.0)), (1, car(2.0))], 3);
+        let mut current = previous.clone();
+        current.remove(0);
+        current[1] = car(5.0);
+        let current = Pool::from_entries(
+            current
+                .into_entries()
+                .into_iter()
+                .chain(vec![(2, car(0.0)), (4, car(0.5))])
+                .collect(),
+            5,
+        );
+        let deltas = car_deltas(&previous, &current);
+        assert!(matches!(deltas[0], CarDelta::Removed(0)));
+        assert!(matches!(deltas[1], CarDelta::Motion(1, _)));
+        assert!(matches!(deltas[2], CarDelta::Full(2, _)));
+        // spawned by another process beyond the previous next ID
+        assert!(matches!(deltas[3], CarDelta::Full(4, _)));
+        let mut view = previous;
+        apply_car_deltas(&mut view, deltas);
+        assert_eq!(view.ids().collect::<Vec<_>>(), vec![1, 2, 4]);
+        assert_eq!(view.next_id(), 5);
+        assert_eq!(view[1].location.lane_position(), Some(5.0));
+    }
+}
@@ -1,4 +1,8 @@
-use crate::communication::CommunicationError;
+use crate::{
+    communication::CommunicationError,
+    controller::car_following::CarFollowingError,
+    model::generate::stateless::{car::CarGenerationError, demand::DemandError},
+};
 use quick_error::quick_error;
 
 quick_error! {
@@ -8,5 +12,20 @@ quick_error! {
             from()
             display("Communication error: {}", err)
         }
+        Demand(err: DemandError) {
+            from()
+            display("Demand error: {}", err)
+        }
+        CarGeneration(err: CarGenerationError) {
+            from()
+            display("Car generation error: {}", err)
+        }
+        CarFollowing(err: CarFollowingError) {
+            from()
+            display("Car following error: {}", err)
+        }
+        InvalidSettings(reason: String) {
+            display("invalid settings: {}", reason)
+        }
     }
 }
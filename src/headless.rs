@@ -0,0 +1,277 @@
+//! Batch runs without a window.
+//!
+//! All processes advance the same fixed simulated duration in fixed steps of
+//! the clock, and the root writes statistics of the simulation instead of
+//! drawing.
+
+use crate::{
+    communication::Traffic,
+    controller::UpdateController,
+    model::{stateful, stateless},
+};
+use mpi::{
+    collective::{CommunicatorCollectives, Root},
+    topology::Rank,
+};
+use piston_window::UpdateArgs;
+use quick_error::quick_error;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::PathBuf,
+};
+use structopt::StructOpt;
+
+quick_error! {
+    #[derive(Debug)]
+    pub enum HeadlessError {
+        Io(err: io::Error) {
+            from()
+            display("IO error: {}", err)
+        }
+        Json(err: serde_json::Error) {
+            from()
+            display("JSON error: {}", err)
+        }
+        InvalidSettings(reason: String) {
+            display("invalid settings: {}", reason)
+        }
+        RootFailed {
+            display("the root failed to create the output")
+        }
+    }
+}
+
+#[derive(StructOpt, Clone, Debug)]
+pub struct HeadlessSettings {
+    /// Run without a window, for nodes without a display
+    #[structopt(name = "headless", long = "headless")]
+    pub enabled: bool,
+    /// Simulated seconds to run
+    #[structopt(
+        name = "headless-duration",
+        long = "headless-duration",
+        default_value = "3600"
+    )]
+    pub duration: f64,
+    /// File of statistics, one JSON object per line
+    #[structopt(
+        name = "headless-output",
+        long = "headless-output",
+        default_value = "mpi-traffic-output.jsonl",
+        parse(from_os_str)
+    )]
+    pub output: PathBuf,
+    /// Steps between written statistics
+    #[structopt(
+        name = "headless-output-interval",
+        long = "headless-output-interval",
+        default_value = "10"
+    )]
+    pub output_interval: u64,
+}
+
+/// Statistics of the simulation known by the root
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Statistics {
+    pub step: u64,
+    pub time: f64,
+    pub cars: usize,
+    pub mean_velocity: f64,
+    /// Cars waiting in sources to enter the city
+    pub waiting: usize,
+    /// Bytes sent and received by the root in the last step of cars
+    pub root_sent: usize,
+    pub root_received: usize,
+}
+
+impl Statistics {
+    pub fn of(stateful: &stateful::Model, traffic: Traffic) -> Self {
+        let cars = stateful.cars.len();
+        let total_velocity: f64 = stateful.cars.iter().map(|(_, car)| car.velocity).sum();
+        let mean_velocity = if cars == 0 {
+            0.0
+        } else {
+            total_velocity / cars as f64
+        };
+        Statistics {
+            step: stateful.step,
+            time: stateful.city.time,
+            cars,
+            mean_velocity,
+            waiting: stateful.city.sources.iter().map(|s| s.waiting).sum(),
+            root_sent: traffic.sent,
+            root_received: traffic.received,
+        }
+    }
+}
+
+/// Run the simulation for the duration of `settings` in steps of `dt`
+/// simulated seconds in all processes.
+///
+/// All processes return an error if the root fails to create the output.
+pub fn run<Comm>(
+    root: Rank,
+    communicator: Comm,
+    settings: &HeadlessSettings,
+    dt: f64,
+    controller: &mut UpdateController,
+    stateful: &mut stateful::Model,
+    stateless: &stateless::Model,
+) -> Result<(), HeadlessError>
+where
+    Comm: CommunicatorCollectives + Clone,
+{
+    if dt <= 0.0 {
+        return Err(HeadlessError::InvalidSettings(format!(
+            "step of {} seconds",
+            dt
+        )));
+    }
+    if settings.duration < 0.0 {
+        return Err(HeadlessError::InvalidSettings(format!(
+            "duration of {} seconds",
+            settings.duration
+        )));
+    }
+    let is_root = communicator.rank() == root;
+    let output = if is_root {
+        Some(File::create(&settings.output))
+    } else {
+        None
+    };
+    let mut created = !matches!(output, Some(Err(_)));
+    communicator
+        .process_at_rank(root)
+        .broadcast_into(&mut created);
+    let mut output = match output {
+        Some(output) => Some(BufWriter::new(output?)),
+        None if created => None,
+        None => return Err(HeadlessError::RootFailed),
+    };
+    let args = UpdateArgs { dt };
+    let steps = (settings.duration / dt).ceil() as u64;
+    let mut since_output = 0;
+    for _ in 0..steps {
+        controller.update(root, communicator.clone(), stateful, stateless, args);
+        since_output += 1;
+        if since_output < settings.output_interval {
+            continue;
+        }
+        since_output = 0;
+        if let Some(output) = output.as_mut() {
+            let statistics = Statistics::of(stateful, controller.last_traffic().total());
+            serde_json::to_writer(&mut *output, &statistics)?;
+            writeln!(output)?;
+        }
+    }
+    if let Some(mut output) = output {
+        output.flush()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        communication::with_world,
+        controller::UpdateSettings,
+        model::{
+            common::{AxisDirection, LaneDirection, RelativeDirection},
+            generate::{self, ModelGenerationSettings},
+            stateful::car::{Location, Trip},
+        },
+        util::pool::Pool,
+    };
+    use std::collections::VecDeque;
+
+    fn model() -> crate::Model {
+        generate::generate_model(ModelGenerationSettings::from_iter(&["test", "--seed", "2"]))
+            .unwrap()
+    }
+
+    #[test]
+    fn statistics_of_model() {
+        let mut stateful = model().stateful;
+        let empty = Statistics::of(&stateful, Traffic::default());
+        assert_eq!(empty.cars, 0);
+        assert!(empty.mean_velocity.abs() < 1e-9);
+
+        let car = |velocity| stateful::Car {
+            location: Location::OnLane {
+                road_direction: AxisDirection::Horizontal,
+                road_index: (0, 0),
+                lane_direction: LaneDirection::LowToHigh,
+                lane_index: 0,
+                about_to_turn: RelativeDirection::Front,
+                position: 0.0,
+            },
+            velocity,
+            acceleration: 0.0,
+            trip: Trip {
+                origin: (0, 0),
+                destination: (0, 0),
+                route: VecDeque::new(),
+            },
+            profile: 0,
+        };
+        stateful.cars = Pool::from_entries(vec![(0, car(10.0)), (3, car(20.0))], 4);
+        stateful.step = 7;
+        stateful.city.sources[0].waiting = 2;
+        let traffic = Traffic {
+            sent: 5,
+            received: 6,
+        };
+        let statistics = Statistics::of(&stateful, traffic);
+        assert_eq!(statistics.step, 7);
+        assert_eq!(statistics.cars, 2);
+        assert!((statistics.mean_velocity - 15.0).abs() < 1e-9);
+        assert_eq!(statistics.waiting, 2);
+        assert_eq!((statistics.root_sent, statistics.root_received), (5, 6));
+    }
+
+    #[test]
+    fn invalid_settings() {
+        let crate::Model {
+            stateless,
+            mut stateful,
+        } = model();
+        let mut controller = UpdateController::new(
+            UpdateSettings::from_iter(&["test"]),
+            &stateless,
+            &stateful,
+            Default::default(),
+        )
+        .unwrap();
+        let mut settings = HeadlessSettings::from_iter(&["test"]);
+        with_world(|world| {
+            let mut run = |settings: &HeadlessSettings, dt| {
+                run(
+                    0,
+                    world,
+                    settings,
+                    dt,
+                    &mut controller,
+                    &mut stateful,
+                    &stateless,
+                )
+            };
+            assert!(matches!(
+                run(&settings, 0.0),
+                Err(HeadlessError::InvalidSettings(_))
+            ));
+            settings.duration = -1.0;
+            assert!(matches!(
+                run(&settings, 0.1),
+                Err(HeadlessError::InvalidSettings(_))
+            ));
+            settings.duration = 1.0;
+            settings.output = std::env::temp_dir()
+                .join(format!("mpi-traffic-missing-{}", std::process::id()))
+                .join("output.jsonl");
+            assert!(matches!(run(&settings, 0.1), Err(HeadlessError::Io(_))));
+        });
+    }
+}
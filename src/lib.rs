@@ -1,6 +1,7 @@
 pub mod communication;
 pub mod controller;
 pub mod error;
+pub mod headless;
 pub mod info;
 pub mod model;
 pub mod util;
@@ -2,19 +2,34 @@ use log::trace;
 use mpi::topology::{Communicator, Rank};
 use mpi_traffic::{
     communication,
-    controller::{Controller, ControllerSettings, UpdateController},
+    controller::{
+        car_following::CarFollowingRegistry, checkpoint::Checkpoint, Command, Controller,
+        ControllerSettings, UpdateController, UpdateSettings,
+    },
+    headless::{self, HeadlessSettings},
     info::Info,
-    model::generate::{self, ModelGenerationSettings},
+    model::{
+        city_file,
+        generate::{self, stateless::generate_stateless_model, ModelGenerationSettings},
+        Model,
+    },
     view::{View, ViewSettings},
 };
-use piston_window::{
-    color, Event, EventLoop, EventSettings, Loop, PistonWindow, UpdateArgs, WindowSettings,
-};
+use piston_window::{color, Event, EventLoop, EventSettings, Loop, PistonWindow, WindowSettings};
+use std::path::PathBuf;
 use structopt::StructOpt;
 
 fn main() {
     env_logger::init();
     let settings = MpiTrafficOpt::from_args();
+    if let Some(Subcommand::Generate { output }) = &settings.command {
+        let stateless =
+            generate_stateless_model(settings.model_generation_settings.stateless_model_settings)
+                .unwrap_or_else(|e| panic!("failed to generate model: {}", e));
+        city_file::save(output, &stateless)
+            .unwrap_or_else(|e| panic!("failed to write {}: {}", output.display(), e));
+        return;
+    }
 
     // Initialize MPI
     let universe = mpi::initialize().unwrap();
@@ -22,16 +37,54 @@ fn main() {
     let world = universe.world();
     let root = world.process_at_rank(ROOT);
 
-    let mut model = if world.rank() == ROOT {
-        generate::generate_model(settings.model_generation_settings)
-    } else {
+    let mut checkpoint = if world.rank() != ROOT {
         Default::default()
+    } else if let Some(path) = &settings.restore {
+        Checkpoint::load(path)
+            .unwrap_or_else(|e| panic!("failed to restore from {}: {}", path.display(), e))
+    } else if let Some(path) = &settings.city_file {
+        let stateless = city_file::load(path, &CarFollowingRegistry::new())
+            .unwrap_or_else(|e| panic!("failed to load {}: {}", path.display(), e));
+        let stateful = generate::stateful::generate_from_stateless(&stateless);
+        Checkpoint {
+            model: Model {
+                stateless,
+                stateful,
+            },
+            ..Default::default()
+        }
+    } else {
+        let model = generate::generate_model(settings.model_generation_settings)
+            .unwrap_or_else(|e| panic!("failed to generate model: {}", e));
+        Checkpoint {
+            model,
+            ..Default::default()
+        }
     };
-    communication::bincode_broadcast(world.rank(), root, &mut model).unwrap();
-    let stateless_model = model.stateless;
-    let mut stateful_model = model.stateful;
+    communication::bincode_broadcast(world.rank(), root, &mut checkpoint).unwrap();
+    let stateless_model = checkpoint.model.stateless;
+    let mut stateful_model = checkpoint.model.stateful;
+    // all processes fail alike on unregistered car following models
+    let mut update_controller = UpdateController::new(
+        settings.update_settings,
+        &stateless_model,
+        &stateful_model,
+        checkpoint.controller,
+    )
+    .unwrap_or_else(|e| panic!("failed to create update controller: {}", e));
 
-    if world.rank() == ROOT {
+    if settings.headless_settings.enabled {
+        headless::run(
+            ROOT,
+            world,
+            &settings.headless_settings,
+            settings.controller_settings.clock_settings.step,
+            &mut update_controller,
+            &mut stateful_model,
+            &stateless_model,
+        )
+        .unwrap_or_else(|e| panic!("failed to run headless: {}", e));
+    } else if world.rank() == ROOT {
         let mut window: PistonWindow = WindowSettings::new("MPI Traffic", [1000, 500])
             .exit_on_esc(true)
             .build()
@@ -41,7 +94,6 @@ fn main() {
 
         let view = View::new(settings.view_settings);
         let mut info = Info::new();
-        let update_controller = UpdateController::new();
         let mut controller = Controller::new(update_controller, settings.controller_settings);
 
         while let Some(e) = window.next() {
@@ -50,43 +102,40 @@ fn main() {
                 use piston_window::clear;
                 let clear_color = color::BLACK;
                 clear(clear_color, g);
-                view.draw(&info, &stateless_model, &stateful_model, c, g);
+                view.draw(
+                    &info,
+                    &stateless_model,
+                    &stateful_model,
+                    controller.interpolation(),
+                    c,
+                    g,
+                );
             });
             match e {
                 Event::Input(e, _) => {
                     controller.input(&mut info, &mut stateful_model, &stateless_model, e);
                 }
-                Event::Loop(Loop::Update(args)) => {
-                    let mut send_args = Some(args);
-                    communication::bincode_broadcast(world.rank(), root, &mut send_args).unwrap();
+                Event::Loop(Loop::Update(_)) => {
                     controller.update(
                         ROOT,
                         world,
                         &mut info,
                         &mut stateful_model,
                         &stateless_model,
-                        args,
                     );
                 }
                 _ => {}
             }
         }
-        communication::bincode_broadcast::<_, Option<UpdateArgs>>(
-            world.rank(),
-            root,
-            &mut Option::None,
-        )
-        .unwrap();
+        communication::bincode_broadcast(world.rank(), root, &mut Command::Exit).unwrap();
     } else {
-        let mut controller = UpdateController::new();
         loop {
-            let mut args: Option<UpdateArgs> = None;
-            communication::bincode_broadcast(world.rank(), root, &mut args).unwrap();
-            if let Some(args) = args {
-                controller.update(ROOT, world, &mut stateful_model, &stateless_model, args);
-            } else {
+            let mut command = Command::Exit;
+            communication::bincode_broadcast(world.rank(), root, &mut command).unwrap();
+            if let Command::Exit = command {
                 break;
             }
+            update_controller.execute(ROOT, world, command, &mut stateful_model, &stateless_model);
         }
     }
 }
@@ -94,12 +143,38 @@ fn main() {
 #[derive(StructOpt)]
 #[structopt(name = "mpi-traffic", about = "Simple traffic simulation with MPI.")]
 struct MpiTrafficOpt {
+    #[structopt(subcommand)]
+    pub command: Option<Subcommand>,
+
+    /// Load the city from a JSON or RON file instead of generating it
+    #[structopt(long = "city-file", parse(from_os_str))]
+    pub city_file: Option<PathBuf>,
+
+    /// Resume from a checkpoint instead of generating a model
+    #[structopt(long = "restore", parse(from_os_str))]
+    pub restore: Option<PathBuf>,
+
     #[structopt(flatten)]
     pub model_generation_settings: ModelGenerationSettings,
 
+    #[structopt(flatten)]
+    pub update_settings: UpdateSettings,
+
     #[structopt(flatten)]
     pub controller_settings: ControllerSettings,
 
     #[structopt(flatten)]
     pub view_settings: ViewSettings,
+
+    #[structopt(flatten)]
+    pub headless_settings: HeadlessSettings,
+}
+
+#[derive(StructOpt)]
+enum Subcommand {
+    /// Generate a city and write it to a JSON or RON file
+    Generate {
+        #[structopt(parse(from_os_str))]
+        output: PathBuf,
+    },
 }
@@ -0,0 +1,399 @@
+//! Human-editable files of stateless models.
+//!
+//! The format is chosen by the extension of the file, `.json` for JSON and
+//! `.ron` for RON. Loaded models are validated, since files are edited by
+//! hand.
+
+use crate::{
+    controller::car_following::CarFollowingRegistry,
+    model::{
+        common::LaneDirection,
+        stateless::{
+            self,
+            car::{CustomDrivingModel, DrivingModel},
+            intersection::SwitchRule,
+            Intersection,
+        },
+    },
+};
+use quick_error::quick_error;
+use std::{
+    fs::{self, File},
+    io::{self, BufReader},
+    path::{Path, PathBuf},
+};
+
+quick_error! {
+    #[derive(Debug)]
+    pub enum CityFileError {
+        Io(err: io::Error) {
+            from()
+            display("IO error: {}", err)
+        }
+        Json(err: serde_json::Error) {
+            from()
+            display("JSON error: {}", err)
+        }
+        Ron(err: ron::Error) {
+            from()
+            display("RON error: {}", err)
+        }
+        UnknownFormat(path: PathBuf) {
+            display("unknown format of city file {}, expected .json or .ron", path.display())
+        }
+        Invalid(reason: String) {
+            display("invalid city: {}", reason)
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CityFileFormat {
+    Json,
+    Ron,
+}
+
+impl CityFileFormat {
+    pub fn of(path: &Path) -> Result<Self, CityFileError> {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => Ok(CityFileFormat::Json),
+            Some("ron") => Ok(CityFileFormat::Ron),
+            _ => Err(CityFileError::UnknownFormat(path.to_path_buf())),
+        }
+    }
+}
+
+/// Load and validate a model, custom car following models must be
+/// registered in `registry`
+pub fn load(
+    path: &Path,
+    registry: &CarFollowingRegistry,
+) -> Result<stateless::Model, CityFileError> {
+    let format = CityFileFormat::of(path)?;
+    let reader = BufReader::new(File::open(path)?);
+    let model = match format {
+        CityFileFormat::Json => serde_json::from_reader(reader)?,
+        CityFileFormat::Ron => ron::de::from_reader(reader)?,
+    };
+    validate(&model, registry)?;
+    Ok(model)
+}
+
+fn positive(x: f64) -> bool {
+    x > 0.0
+}
+
+fn invalid<S: Into<String>>(reason: S) -> Result<(), CityFileError> {
+    Err(CityFileError::Invalid(reason.into()))
+}
+
+/// Check that shapes of the board and the geometry agree, roads and lanes
+/// fit their intersections, sources and sinks are intersections, and custom
+/// car following models are registered.
+pub fn validate(
+    model: &stateless::Model,
+    registry: &CarFollowingRegistry,
+) -> Result<(), CityFileError> {
+    let city = &model.city;
+    let board = &city.board;
+    let (m, n) = board.shape();
+    if m == 0 || n == 0 {
+        return invalid("empty board");
+    }
+    let matrices = [
+        (
+            "intersections",
+            board.intersections.shape,
+            board.intersections.storage.len(),
+            (m, n),
+        ),
+        (
+            "horizontal roads",
+            board.horizontal_roads.shape,
+            board.horizontal_roads.storage.len(),
+            (m, n - 1),
+        ),
+        (
+            "vertical roads",
+            board.vertical_roads.shape,
+            board.vertical_roads.storage.len(),
+            (m - 1, n),
+        ),
+    ];
+    for (name, shape, len, expected) in matrices.iter() {
+        if shape != expected || *len != expected.0 * expected.1 {
+            return invalid(format!(
+                "{} of shape {:?} with {} elements, expected shape {:?}",
+                name, shape, len, expected
+            ));
+        }
+    }
+    let lengths = [
+        (
+            "horizontal_road_length",
+            city.horizontal_road_length.len(),
+            n - 1,
+        ),
+        (
+            "vertical_road_length",
+            city.vertical_road_length.len(),
+            m - 1,
+        ),
+        ("intersection_height", city.intersection_height.len(), m),
+        ("intersection_width", city.intersection_width.len(), n),
+    ];
+    for (name, len, expected) in lengths.iter() {
+        if len != expected {
+            return invalid(format!(
+                "{} of {} lengths, expected {}",
+                name, len, expected
+            ));
+        }
+    }
+
+    for (index, (direction, road)) in board.enumerate_roads() {
+        let road = match road {
+            Some(road) => road,
+            None => continue,
+        };
+        if road.lane_number() == 0 {
+            return invalid(format!("{:?} road {:?} without lanes", direction, index));
+        }
+        for lane_direction in LaneDirection::directions() {
+            let lanes = road.lanes_to_direction(*lane_direction);
+            // lane indices are sent in a byte
+            if lanes.len() > u8::MAX as usize {
+                return invalid(format!(
+                    "{:?} road {:?} with {} lanes to {:?}",
+                    direction,
+                    index,
+                    lanes.len(),
+                    lane_direction
+                ));
+            }
+            if lanes.iter().any(|lane| !positive(lane.max_speed)) {
+                return invalid(format!(
+                    "{:?} road {:?} with a lane to {:?} without speed",
+                    direction, index, lane_direction
+                ));
+            }
+            // cars must leave the road somewhere
+            if !lanes.is_empty() && road.turn_rule_to_direction(*lane_direction).is_empty() {
+                return invalid(format!(
+                    "{:?} road {:?} without turns to {:?}",
+                    direction, index, lane_direction
+                ));
+            }
+        }
+    }
+
+    for (index, intersection) in board.intersections.enumerate() {
+        let context = board.context_of_intersection(index);
+        let max_speed = match intersection {
+            Some(Intersection::Crossroad { max_speed, .. })
+            | Some(Intersection::TJunction { max_speed, .. })
+            | Some(Intersection::Turn { max_speed })
+            | Some(Intersection::End { max_speed }) => Some(*max_speed),
+            Some(Intersection::Straight) | None => None,
+        };
+        if matches!(max_speed, Some(speed) if !positive(speed)) {
+            return invalid(format!("intersection {:?} without speed", index));
+        }
+        let expected = match intersection {
+            None => 0,
+            Some(Intersection::Crossroad { .. }) => 4,
+            Some(Intersection::TJunction { .. }) => 3,
+            Some(Intersection::Turn { .. }) | Some(Intersection::Straight) => 2,
+            Some(Intersection::End { .. }) => 1,
+        };
+        if context.road_number() != expected {
+            return invalid(format!(
+                "intersection {:?} with {} roads, expected {}",
+                index,
+                context.road_number(),
+                expected
+            ));
+        }
+        let (rules, SwitchRule::LoopTimeout { times }) = match intersection {
+            Some(Intersection::Crossroad {
+                rules, switch_rule, ..
+            }) => (rules.len(), switch_rule),
+            Some(Intersection::TJunction {
+                single,
+                rule_set,
+                switch_rule,
+                ..
+            }) => {
+                if context.get(single.turn_back()).is_some() {
+                    return invalid(format!(
+                        "T-junction {:?} with a road opposite to its single arm",
+                        index
+                    ));
+                }
+                (rule_set.len(), switch_rule)
+            }
+            _ => continue,
+        };
+        if rules == 0 || times.is_empty() || !times.iter().copied().all(positive) {
+            return invalid(format!(
+                "intersection {:?} without signal rules or timeouts",
+                index
+            ));
+        }
+    }
+
+    let is_intersection = |index| matches!(board.intersections.get(index), Some(Some(_)));
+    for source in city.sources.iter() {
+        if !is_intersection(source.intersection) {
+            return invalid(format!(
+                "source at {:?} is not an intersection",
+                source.intersection
+            ));
+        }
+    }
+    for sink in city.sinks.iter() {
+        if !is_intersection(*sink) {
+            return invalid(format!("sink at {:?} is not an intersection", sink));
+        }
+    }
+
+    for car in model.cars.iter() {
+        if let DrivingModel::Custom(CustomDrivingModel { name, .. }) = &car.driving_model {
+            if !registry.is_registered(name) {
+                return invalid(format!("car following model {} is not registered", name));
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn save(path: &Path, model: &stateless::Model) -> Result<(), CityFileError> {
+    let serialized = match CityFileFormat::of(path)? {
+        CityFileFormat::Json => serde_json::to_string_pretty(model)?,
+        CityFileFormat::Ron => ron::ser::to_string_pretty(model, Default::default())?,
+    };
+    fs::write(path, serialized)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::common::TurnRule;
+    use crate::model::generate::stateless::{
+        generate_stateless_model, StatelessModelGenerationSettings,
+    };
+    use structopt::StructOpt;
+
+    fn model() -> stateless::Model {
+        let settings = StatelessModelGenerationSettings::from_iter(&["test", "--seed", "2"]);
+        generate_stateless_model(settings).unwrap()
+    }
+
+    #[test]
+    fn save_and_load() {
+        let model = model();
+        let registry = CarFollowingRegistry::new();
+        for extension in &["json", "ron"] {
+            let path = std::env::temp_dir().join(format!(
+                "mpi-traffic-city-{}.{}",
+                std::process::id(),
+                extension
+            ));
+            save(&path, &model).unwrap();
+            let loaded = load(&path, &registry).unwrap();
+            fs::remove_file(&path).unwrap();
+            assert_eq!(loaded.seed, model.seed);
+            assert_eq!(loaded.cars.len(), model.cars.len());
+            assert_eq!(
+                loaded.city.board.intersections.shape(),
+                model.city.board.intersections.shape()
+            );
+        }
+    }
+
+    #[test]
+    fn unknown_format() {
+        assert!(CityFileFormat::of(Path::new("city.toml")).is_err());
+    }
+
+    fn assert_invalid<F: FnOnce(&mut stateless::Model)>(change: F) {
+        let mut model = model();
+        change(&mut model);
+        match validate(&model, &CarFollowingRegistry::new()) {
+            Err(CityFileError::Invalid(_)) => (),
+            result => panic!("validated a changed model: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn invalid_models() {
+        validate(&model(), &CarFollowingRegistry::new()).unwrap();
+        assert_invalid(|model| {
+            model.city.horizontal_road_length.pop();
+        });
+        assert_invalid(|model| {
+            model.city.board.vertical_roads.storage.pop();
+        });
+        assert_invalid(|model| model.city.sinks.push((100, 0)));
+        assert_invalid(|model| {
+            let (m, _) = model.city.board.shape();
+            model.city.sources[0].intersection = (m, 0);
+        });
+        assert_invalid(|model| {
+            let road = model
+                .city
+                .board
+                .horizontal_roads
+                .iter_mut()
+                .find_map(|road| road.as_mut())
+                .unwrap();
+            road.lane_to_high.clear();
+            road.lane_to_low.clear();
+        });
+        assert_invalid(|model| {
+            let road = model
+                .city
+                .board
+                .horizontal_roads
+                .iter_mut()
+                .find_map(|road| road.as_mut())
+                .unwrap();
+            road.lane_to_high
+                .iter_mut()
+                .for_each(|lane| lane.direction_rule = TurnRule::empty());
+        });
+        // a road without an intersection at its end
+        assert_invalid(|model| {
+            let board = &mut model.city.board;
+            let (index, _) = board
+                .horizontal_roads
+                .enumerate()
+                .find(|(_, road)| road.is_some())
+                .unwrap();
+            board.intersections[index] = None;
+        });
+        assert_invalid(|model| {
+            let intersection = model
+                .city
+                .board
+                .intersections
+                .iter_mut()
+                .find_map(|intersection| match intersection {
+                    Some(Intersection::Crossroad { max_speed, .. })
+                    | Some(Intersection::TJunction { max_speed, .. })
+                    | Some(Intersection::Turn { max_speed })
+                    | Some(Intersection::End { max_speed }) => Some(max_speed),
+                    _ => None,
+                })
+                .unwrap();
+            *intersection = 0.0;
+        });
+        assert_invalid(|model| {
+            model.cars[0].driving_model = DrivingModel::Custom(CustomDrivingModel {
+                name: "unknown".to_string(),
+                parameters: vec![],
+            })
+        });
+    }
+}
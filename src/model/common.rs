@@ -6,6 +6,7 @@ use serde::{Deserialize, Serialize};
 
 use bitflags::bitflags;
 
+/// Stable ID of a car in `stateful::Model::cars`
 pub type CarIndex = usize;
 pub type LaneIndex = usize;
 
@@ -46,6 +47,12 @@ impl RelativeDirection {
             Left => TurnRule::LEFT,
         }
     }
+
+    pub fn directions() -> std::slice::Iter<'static, RelativeDirection> {
+        use RelativeDirection::*;
+        static DIRECTIONS: [RelativeDirection; 4] = [Front, Right, Left, Back];
+        DIRECTIONS.iter()
+    }
 }
 
 impl AbsoluteDirection {
@@ -289,6 +296,32 @@ impl Position {
         let dy = y1 - y2;
         (dx * dx + dy * dy).sqrt()
     }
+
+    /// Intersection of segment `self`-`end` and segment `other`-`other_end`.
+    ///
+    /// Return the proportions of the intersection point on both segments, or
+    /// `None` if the segments are parallel or do not intersect.
+    pub fn segment_intersection(
+        self,
+        end: Self,
+        other: Self,
+        other_end: Self,
+    ) -> Option<(f64, f64)> {
+        let (dx, dy) = (end.x - self.x, end.y - self.y);
+        let (other_dx, other_dy) = (other_end.x - other.x, other_end.y - other.y);
+        let denominator = dx * other_dy - dy * other_dx;
+        if denominator.abs() < f64::EPSILON {
+            return None;
+        }
+        let (ox, oy) = (other.x - self.x, other.y - self.y);
+        let proportion = (ox * other_dy - oy * other_dx) / denominator;
+        let other_proportion = (ox * dy - oy * dx) / denominator;
+        if (0.0..=1.0).contains(&proportion) && (0.0..=1.0).contains(&other_proportion) {
+            Some((proportion, other_proportion))
+        } else {
+            None
+        }
+    }
 }
 
 #[cfg(test)]
@@ -363,4 +396,36 @@ mod test {
             assert_eq!(a.distance(b), result);
         }
     }
+
+    #[test]
+    fn segment_intersection() {
+        let origin = Position { x: 0.0, y: 0.0 };
+        let (proportion, other_proportion) = origin
+            .segment_intersection(
+                Position { x: 4.0, y: 0.0 },
+                Position { x: 1.0, y: -1.0 },
+                Position { x: 1.0, y: 3.0 },
+            )
+            .unwrap();
+        assert!((proportion - 0.25).abs() < 1e-9);
+        assert!((other_proportion - 0.25).abs() < 1e-9);
+        // parallel
+        assert_eq!(
+            origin.segment_intersection(
+                Position { x: 4.0, y: 0.0 },
+                Position { x: 0.0, y: 1.0 },
+                Position { x: 4.0, y: 1.0 },
+            ),
+            None
+        );
+        // lines intersect out of the segments
+        assert_eq!(
+            origin.segment_intersection(
+                Position { x: 4.0, y: 0.0 },
+                Position { x: 5.0, y: -1.0 },
+                Position { x: 5.0, y: 1.0 },
+            ),
+            None
+        );
+    }
 }
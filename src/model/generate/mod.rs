@@ -1,9 +1,12 @@
-use crate::model::{
-    generate::{
-        stateful::generate_from_stateless,
-        stateless::{generate_stateless_model, StatelessModelGenerationSettings},
+use crate::{
+    model::{
+        generate::{
+            stateful::generate_from_stateless,
+            stateless::{generate_stateless_model, StatelessModelGenerationSettings},
+        },
+        Model,
     },
-    Model,
+    Error,
 };
 use structopt::StructOpt;
 
@@ -16,11 +19,11 @@ pub struct ModelGenerationSettings {
     pub stateless_model_settings: StatelessModelGenerationSettings,
 }
 
-pub fn generate_model(model_settings: ModelGenerationSettings) -> Model {
-    let stateless_model = generate_stateless_model(model_settings.stateless_model_settings);
+pub fn generate_model(model_settings: ModelGenerationSettings) -> Result<Model, Error> {
+    let stateless_model = generate_stateless_model(model_settings.stateless_model_settings)?;
     let stateful_model = generate_from_stateless(&stateless_model);
-    Model {
+    Ok(Model {
         stateless: stateless_model,
         stateful: stateful_model,
-    }
+    })
 }
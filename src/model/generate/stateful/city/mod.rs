@@ -1,11 +1,22 @@
-use crate::model::{board::Board, stateful::City, stateless};
+use crate::model::{
+    board::Board,
+    stateful::{City, Source},
+    stateless,
+};
 
 pub mod intersection;
 pub mod road;
 
-pub fn generate_city_from_stateless(stateless_model: &stateless::City) -> City {
+pub fn generate_city_from_stateless(stateless_model: &stateless::City, seed: u64) -> City {
     let mut city = City {
         board: Board::with_shape(None, (), stateless_model.board.shape()),
+        sources: stateless_model
+            .sources
+            .iter()
+            .enumerate()
+            .map(|(index, source)| Source::new(&source.arrival, seed, index))
+            .collect(),
+        time: 0.0,
     };
     for index in stateless_model.board.intersections.indices() {
         if let Some(stateless_intersection) = &stateless_model.board.intersections[index] {
@@ -1,32 +1,306 @@
-use rand::{self, Rng};
+use quick_error::quick_error;
+use rand::{
+    distributions::{Distribution, WeightedError, WeightedIndex},
+    Rng,
+};
+use std::{ops::RangeInclusive, str::FromStr};
 
-use crate::model::{
-    generate::stateless::StatelessModelGenerationSettings,
-    stateless::{car::DrivingModel, Car},
+use crate::{
+    model::{
+        generate::stateless::StatelessModelGenerationSettings,
+        stateless::{
+            car::{
+                DrivingModel, IntelligentDriverModel, LaneChangeModel, NormalDrivingModel,
+                VehicleClass,
+            },
+            Car,
+        },
+    },
+    util::rng::{self, Stream},
 };
 
-pub fn generate_cars(settings: &StatelessModelGenerationSettings) -> Vec<Car> {
-    (0..settings.initial_car_number)
-        .map(|_| generate_car(settings))
-        .collect()
+quick_error! {
+    #[derive(Debug)]
+    pub enum CarGenerationError {
+        ClassMix(err: WeightedError) {
+            from()
+            display("invalid vehicle class mix: {}", err)
+        }
+        ClassMixLength(len: usize) {
+            display("vehicle class mix of {} proportions, expected {}", len, VehicleClass::classes().len())
+        }
+        ClassParameters(class: VehicleClass, name: &'static str) {
+            display("invalid {} of {:?}", name, class)
+        }
+    }
 }
 
-fn generate_car(settings: &StatelessModelGenerationSettings) -> Car {
-    let mut rng = rand::thread_rng();
-    Car {
-        max_velocity: rng.gen_range(settings.min_max_velocity..=settings.max_max_velocity),
-        max_acceleration: rng
-            .gen_range(settings.min_max_acceleration..=settings.max_max_acceleration),
-        max_break_acceleration: rng
-            .gen_range(settings.min_max_break_acceleration..=settings.max_max_break_acceleration),
-        lane_change_time: rng
-            .gen_range(settings.min_lane_change_time..=settings.max_lane_change_time),
-        driving_model: DrivingModel::Normal {
+/// Generate car profiles, each profile is generated from its own random stream
+pub fn generate_cars(
+    settings: &StatelessModelGenerationSettings,
+    seed: u64,
+) -> Result<Vec<Car>, CarGenerationError> {
+    let mix = &settings.vehicle_class_mix;
+    if mix.len() != VehicleClass::classes().len() {
+        return Err(CarGenerationError::ClassMixLength(mix.len()));
+    }
+    let classes = WeightedIndex::new(mix)?;
+    let distributions = VehicleClass::classes()
+        .map(|class| ClassDistribution::of(*class, settings))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok((0..settings.car_profile_number)
+        .map(|index| {
+            generate_car(
+                settings,
+                &classes,
+                &distributions,
+                &mut rng::stream_rng(seed, Stream::Car, index as u64, 0),
+            )
+        })
+        .collect())
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DrivingModelKind {
+    Normal,
+    IntelligentDriver,
+}
+
+impl FromStr for DrivingModelKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "normal" => Ok(DrivingModelKind::Normal),
+            "intelligent-driver" | "idm" => Ok(DrivingModelKind::IntelligentDriver),
+            _ => Err(format!("unknown driving model: {}", s)),
+        }
+    }
+}
+
+/// Distributions of parameters of a vehicle class
+struct ClassDistribution {
+    class: VehicleClass,
+    length: RangeInclusive<f64>,
+    width: RangeInclusive<f64>,
+    max_velocity: RangeInclusive<f64>,
+    max_acceleration: RangeInclusive<f64>,
+    max_break_acceleration: RangeInclusive<f64>,
+    lane_change_time: RangeInclusive<f64>,
+    time_headway: RangeInclusive<f64>,
+    driving_model: DrivingModelKind,
+}
+
+impl ClassDistribution {
+    /// Cars use the ranges of the settings, other classes are sized by their
+    /// own settings and scale the car ranges of velocity, acceleration and
+    /// timing by their factors.
+    fn of(
+        class: VehicleClass,
+        settings: &StatelessModelGenerationSettings,
+    ) -> Result<Self, CarGenerationError> {
+        let invalid = |name| CarGenerationError::ClassParameters(class, name);
+        let range = |values: &[f64], name| match values {
+            [min, max] if 0.0 < *min && min <= max => Ok(*min..=*max),
+            _ => Err(invalid(name)),
+        };
+        let (length, width, factors) = match class {
+            VehicleClass::Car => (
+                range(
+                    &[settings.min_car_length, settings.max_car_length],
+                    "length",
+                )?,
+                range(&[settings.min_car_width, settings.max_car_width], "width")?,
+                &[1.0; 4][..],
+            ),
+            VehicleClass::Bus => (
+                range(&settings.bus_length, "length")?,
+                range(&settings.bus_width, "width")?,
+                &settings.bus_factors[..],
+            ),
+            VehicleClass::Truck => (
+                range(&settings.truck_length, "length")?,
+                range(&settings.truck_width, "width")?,
+                &settings.truck_factors[..],
+            ),
+            VehicleClass::Motorcycle => (
+                range(&settings.motorcycle_length, "length")?,
+                range(&settings.motorcycle_width, "width")?,
+                &settings.motorcycle_factors[..],
+            ),
+        };
+        let (velocity, acceleration, break_acceleration, timing) = match *factors {
+            [velocity, acceleration, break_acceleration, timing]
+                if factors.iter().all(|factor| *factor > 0.0) =>
+            {
+                (velocity, acceleration, break_acceleration, timing)
+            }
+            _ => return Err(invalid("factors")),
+        };
+        let scale = |min: f64, max: f64, factor: f64| min * factor..=max * factor;
+        let driving_model = match class {
+            VehicleClass::Bus | VehicleClass::Truck => settings.heavy_driving_model,
+            VehicleClass::Car | VehicleClass::Motorcycle => settings.driving_model,
+        };
+        Ok(ClassDistribution {
+            class,
+            length,
+            width,
+            max_velocity: scale(
+                settings.min_max_velocity,
+                settings.max_max_velocity,
+                velocity,
+            ),
+            max_acceleration: scale(
+                settings.min_max_acceleration,
+                settings.max_max_acceleration,
+                acceleration,
+            ),
+            max_break_acceleration: scale(
+                settings.min_max_break_acceleration,
+                settings.max_max_break_acceleration,
+                break_acceleration,
+            ),
+            lane_change_time: scale(
+                settings.min_lane_change_time,
+                settings.max_lane_change_time,
+                timing,
+            ),
+            time_headway: scale(settings.min_time_headway, settings.max_time_headway, timing),
+            driving_model,
+        })
+    }
+}
+
+/// Generate a car of a class chosen by the class mix, `distributions` are
+/// in the order of `VehicleClass::classes`
+fn generate_car<R: Rng>(
+    settings: &StatelessModelGenerationSettings,
+    classes: &WeightedIndex<f64>,
+    distributions: &[ClassDistribution],
+    rng: &mut R,
+) -> Car {
+    let distribution = &distributions[classes.sample(rng)];
+    let class = distribution.class;
+    let max_velocity = rng.gen_range(distribution.max_velocity.clone());
+    let max_acceleration = rng.gen_range(distribution.max_acceleration.clone());
+    let driving_model = match distribution.driving_model {
+        DrivingModelKind::Normal => DrivingModel::Normal(NormalDrivingModel {
             min_cushion: settings.min_cushion,
             cushion_velocity_factor: rng.gen_range(
                 settings.min_cushion_velocity_factor..=settings.max_cushion_velocity_factor,
             ),
             prediction_time: settings.prediction_time,
+        }),
+        DrivingModelKind::IntelligentDriver => {
+            DrivingModel::IntelligentDriver(IntelligentDriverModel {
+                desired_velocity: max_velocity,
+                time_headway: rng.gen_range(distribution.time_headway.clone()),
+                min_gap: settings.min_gap,
+                max_acceleration,
+                comfortable_deceleration: settings.comfortable_deceleration,
+                acceleration_exponent: settings.acceleration_exponent,
+            })
+        }
+    };
+    Car {
+        class,
+        length: rng.gen_range(distribution.length.clone()),
+        width: rng.gen_range(distribution.width.clone()),
+        max_velocity,
+        max_acceleration,
+        max_break_acceleration: rng.gen_range(distribution.max_break_acceleration.clone()),
+        lane_change_time: rng.gen_range(distribution.lane_change_time.clone()),
+        critical_gap: rng.gen_range(settings.min_critical_gap..=settings.max_critical_gap),
+        driving_model,
+        lane_change_model: LaneChangeModel::Mobil {
+            politeness: rng.gen_range(settings.min_politeness..=settings.max_politeness),
+            threshold: settings.lane_change_threshold,
+            bias: settings.lane_change_bias,
+            safe_break_acceleration: settings.lane_change_safe_break_acceleration,
         },
+        informed: rng.gen::<f64>() < settings.informed_proportion,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use structopt::StructOpt;
+
+    #[test]
+    fn class_mix() {
+        let settings = StatelessModelGenerationSettings::from_iter(&[
+            "test",
+            "--stateless-model-generation-car-profile-number",
+            "10",
+            "--stateless-model-generation-vehicle-class-mix",
+            "0,1,0,0",
+        ]);
+        let cars = generate_cars(&settings, 0).unwrap();
+        assert_eq!(cars.len(), 10);
+        for car in cars {
+            assert_eq!(car.class, VehicleClass::Bus);
+            assert!((car.length - 12.0).abs() < 1e-9);
+            assert!(matches!(
+                car.driving_model,
+                DrivingModel::IntelligentDriver(_)
+            ));
+        }
+    }
+
+    #[test]
+    fn class_parameters() {
+        let settings = StatelessModelGenerationSettings::from_iter(&[
+            "test",
+            "--stateless-model-generation-car-profile-number",
+            "10",
+            "--stateless-model-generation-vehicle-class-mix",
+            "0,0,1,0",
+            "--stateless-model-generation-truck-length",
+            "10,10",
+            "--stateless-model-generation-truck-factors",
+            "0.5,0.5,0.5,1",
+        ]);
+        for car in generate_cars(&settings, 0).unwrap() {
+            assert_eq!(car.class, VehicleClass::Truck);
+            assert!((car.length - 10.0).abs() < 1e-9);
+            assert!(car.max_velocity <= settings.max_max_velocity * 0.5);
+        }
+    }
+
+    fn generation_error(args: &[&str]) -> CarGenerationError {
+        let settings =
+            StatelessModelGenerationSettings::from_iter(["test"].iter().chain(args.iter()));
+        generate_cars(&settings, 0).unwrap_err()
+    }
+
+    #[test]
+    fn invalid_class_parameters() {
+        let mix = "--stateless-model-generation-vehicle-class-mix";
+        assert!(matches!(
+            generation_error(&[mix, "0,0,0,0"]),
+            CarGenerationError::ClassMix(_)
+        ));
+        assert!(matches!(
+            generation_error(&[mix, "1,-1,0,0"]),
+            CarGenerationError::ClassMix(_)
+        ));
+        assert!(matches!(
+            generation_error(&[mix, "1,1"]),
+            CarGenerationError::ClassMixLength(2)
+        ));
+        assert!(matches!(
+            generation_error(&["--stateless-model-generation-bus-length", "12,8"]),
+            CarGenerationError::ClassParameters(VehicleClass::Bus, "length")
+        ));
+        assert!(matches!(
+            generation_error(&["--stateless-model-generation-motorcycle-width", "1"]),
+            CarGenerationError::ClassParameters(VehicleClass::Motorcycle, "width")
+        ));
+        assert!(matches!(
+            generation_error(&["--stateless-model-generation-truck-factors", "1,1,0,1"]),
+            CarGenerationError::ClassParameters(VehicleClass::Truck, "factors")
+        ));
     }
 }
@@ -84,11 +84,18 @@ fn add_lane(
         })
         .unwrap();
     let lane_direction = LaneDirection::absolute_in_out_to_lane(direction, in_out_direction);
-    board.get_roads_mut(direction.axis_direction())[index]
+    let road = board.get_roads_mut(direction.axis_direction())[index]
         .as_mut()
-        .unwrap()
-        .lanes_to_direction_mut(lane_direction)
-        .push(basic_lane(lane_max_speed));
+        .unwrap();
+    // keep the speed zone of the road
+    let max_speed = road
+        .lane_to_high
+        .iter()
+        .chain(road.lane_to_low.iter())
+        .map(|lane| lane.max_speed)
+        .fold(lane_max_speed, f64::min);
+    road.lanes_to_direction_mut(lane_direction)
+        .push(basic_lane(max_speed));
 }
 
 fn fix_lane_direction_rule(board: &mut Board<Option<Intersection>, Option<Road>>) {
@@ -99,7 +99,44 @@ fn generate_with_3_road(
 }
 
 fn generate_with_4_road(settings: &StatelessModelGenerationSettings) -> Intersection {
-    let rules = vec![
+    let rules = if settings.permissive_left_turn {
+        permissive_crossroad_rules()
+    } else {
+        protected_crossroad_rules()
+    };
+    let switch_rule = SwitchRule::LoopTimeout {
+        times: vec![settings.time_out],
+    };
+    Intersection::Crossroad {
+        max_speed: settings.intersection_max_speed,
+        rules,
+        switch_rule,
+    }
+}
+
+/// Left turns yield to opposing straight traffic in the same phase
+fn permissive_crossroad_rules() -> Vec<CrossroadRule> {
+    let green = TurnRule::FRONT | TurnRule::LEFT | TurnRule::RIGHT | TurnRule::BACK;
+    let red = TurnRule::RIGHT | TurnRule::BACK;
+    vec![
+        CrossroadRule {
+            north: green,
+            south: green,
+            east: red,
+            west: red,
+        },
+        CrossroadRule {
+            east: green,
+            west: green,
+            north: red,
+            south: red,
+        },
+    ]
+}
+
+/// Left turns have their own phases
+fn protected_crossroad_rules() -> Vec<CrossroadRule> {
+    vec![
         CrossroadRule {
             north: TurnRule::FRONT | TurnRule::RIGHT | TurnRule::BACK,
             south: TurnRule::FRONT | TurnRule::RIGHT | TurnRule::BACK,
@@ -124,13 +161,5 @@ fn generate_with_4_road(settings: &StatelessModelGenerationSettings) -> Intersec
             south: TurnRule::RIGHT | TurnRule::BACK,
             north: TurnRule::RIGHT | TurnRule::BACK,
         },
-    ];
-    let switch_rule = SwitchRule::LoopTimeout {
-        times: vec![settings.time_out],
-    };
-    Intersection::Crossroad {
-        max_speed: settings.intersection_max_speed,
-        rules,
-        switch_rule,
-    }
+    ]
 }
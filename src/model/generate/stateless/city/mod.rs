@@ -1,7 +1,7 @@
-use rand::{self, Rng};
+use rand::Rng;
 
 use crate::model::{
-    board::{Board, IntersectionIndex},
+    board::Board,
     generate::stateless::StatelessModelGenerationSettings,
     stateless::{City, Intersection, Road},
 };
@@ -9,49 +9,48 @@ use crate::model::{
 mod fix;
 pub mod intersection;
 pub mod road;
+pub mod source;
 
 pub const MIN_LANE_LENGTH: f64 = 50.0;
 pub const MAX_LANE_LENGTH: f64 = 100.0;
 
-pub fn generate_city(city_settings: &StatelessModelGenerationSettings) -> City {
+pub fn generate_city<R: Rng>(
+    city_settings: &StatelessModelGenerationSettings,
+    rng: &mut R,
+) -> City {
     let board_shape = (
         city_settings.board_shape_rows,
         city_settings.board_shape_cols,
     );
     let mut board = Board::with_shape(None, None, board_shape);
-    road::generate_roads(&mut board, &city_settings);
+    road::generate_roads(&mut board, &city_settings, rng);
     intersection::generate_intersections(&mut board, &city_settings);
     fix::fix(&mut board, &city_settings);
 
     let (intersection_height, intersection_width) =
         calculate_intersection_geometry(&board, city_settings.lane_width);
-    let car_out_intersection = generate_car_out_intersection(&board, city_settings);
+    let horizontal_road_length = rand_road_length(board_shape.1 - 1, city_settings, rng);
+    let vertical_road_length = rand_road_length(board_shape.0 - 1, city_settings, rng);
+    let sinks = source::generate_sinks(&board);
+    let sources = source::generate_sources(&sinks, city_settings, rng);
     City {
         board,
-        car_out_intersection,
+        sources,
+        sinks,
         car_out_min_distance: city_settings.car_out_min_distance,
         lane_width: city_settings.lane_width,
-        horizontal_road_length: rand_road_length(board_shape.1 - 1, city_settings),
-        vertical_road_length: rand_road_length(board_shape.0 - 1, city_settings),
+        horizontal_road_length,
+        vertical_road_length,
         intersection_height,
         intersection_width,
     }
 }
 
-fn generate_car_out_intersection(
-    board: &Board<Option<Intersection>, Option<Road>>,
-    _city_settings: &StatelessModelGenerationSettings,
-) -> IntersectionIndex {
-    for (index, intersection) in board.intersections.enumerate() {
-        if intersection.is_some() {
-            return index;
-        }
-    }
-    panic!("empty city")
-}
-
-fn rand_road_length(road_num: usize, settings: &StatelessModelGenerationSettings) -> Vec<f64> {
-    let mut rng = rand::thread_rng();
+fn rand_road_length<R: Rng>(
+    road_num: usize,
+    settings: &StatelessModelGenerationSettings,
+    rng: &mut R,
+) -> Vec<f64> {
     (0..road_num)
         .map(|_| rng.gen_range(settings.min_road_length..=settings.max_road_length))
         .collect()
@@ -1,3 +1,5 @@
+use rand::Rng;
+
 use crate::model::{
     board::Board,
     common::{LaneDirection, TurnRule},
@@ -5,12 +7,13 @@ use crate::model::{
     stateless::{Intersection, Lane, Road},
 };
 
-pub fn generate_roads(
+pub fn generate_roads<R: Rng>(
     board: &mut Board<Option<Intersection>, Option<Road>>,
     generation_settings: &StatelessModelGenerationSettings,
+    rng: &mut R,
 ) {
     generate_basic_board(board, generation_settings);
-    mutate_board(board, generation_settings);
+    mutate_board(board, generation_settings, rng);
 }
 
 #[inline]
@@ -34,47 +37,76 @@ fn generate_basic_board(
 ) {
     board.roads_mut().for_each(|(_, road)| {
         *road = Some(basic_road(
-            generation_settings.lane_width,
+            generation_settings.lane_max_speed,
             generation_settings.default_lane_num,
         ))
     });
 }
 
-fn mutate_board(
+fn mutate_board<R: Rng>(
     board: &mut Board<Option<Intersection>, Option<Road>>,
     generation_settings: &StatelessModelGenerationSettings,
+    rng: &mut R,
 ) {
-    generate_one_way(board, generation_settings);
-    remove_road(board, generation_settings);
-    add_straight_long_way(board, generation_settings);
+    generate_one_way(board, generation_settings, rng);
+    remove_road(board, generation_settings, rng);
+    add_straight_long_way(board, generation_settings, rng);
+    generate_speed_zones(board, generation_settings, rng);
 }
 
-fn remove_road(
+/// Lower the max speed of all lanes of some roads
+fn generate_speed_zones<R: Rng>(
     board: &mut Board<Option<Intersection>, Option<Road>>,
     settings: &StatelessModelGenerationSettings,
+    rng: &mut R,
 ) {
     board
         .roads_mut()
-        .filter(|(_, road)| road.is_some() && rand::random::<f64>() < settings.empty_proportion)
-        .for_each(|(_, road)| {
-            road.take();
-        })
+        .filter_map(|(_, road)| road.as_mut())
+        .filter(|_| rng.gen::<f64>() < settings.speed_zone_proportion)
+        .for_each(|road| {
+            road.lane_to_high
+                .iter_mut()
+                .chain(road.lane_to_low.iter_mut())
+                .for_each(|lane| {
+                    lane.max_speed = lane.max_speed.min(settings.speed_zone_max_speed)
+                });
+        });
 }
 
-fn generate_one_way(
+fn remove_road<R: Rng>(
     board: &mut Board<Option<Intersection>, Option<Road>>,
     settings: &StatelessModelGenerationSettings,
+    rng: &mut R,
 ) {
     board
         .roads_mut()
-        .filter(|(_, road)| road.is_some() && rand::random::<f64>() < settings.one_way_proportion)
+        .filter(|(_, road)| road.is_some() && rng.gen::<f64>() < settings.empty_proportion)
         .for_each(|(_, road)| {
-            convert_to_one_way(road.as_mut().unwrap(), settings);
-        });
+            road.take();
+        })
+}
+
+fn generate_one_way<R: Rng>(
+    board: &mut Board<Option<Intersection>, Option<Road>>,
+    settings: &StatelessModelGenerationSettings,
+    rng: &mut R,
+) {
+    for (_, road) in board.roads_mut() {
+        if let Some(road) = road {
+            if rng.gen::<f64>() < settings.one_way_proportion {
+                convert_to_one_way(road, settings, rng);
+            }
+        }
+    }
 }
 
-fn convert_to_one_way(road: &mut Road, settings: &StatelessModelGenerationSettings) {
-    let one_way_direction = rand::random::<LaneDirection>();
+fn convert_to_one_way<R: Rng>(
+    road: &mut Road,
+    settings: &StatelessModelGenerationSettings,
+    rng: &mut R,
+) {
+    let one_way_direction = rng.gen::<LaneDirection>();
     road.lanes_to_direction_mut(one_way_direction.opposite())
         .clear();
     let lanes = &mut road.lanes_to_direction_mut(one_way_direction);
@@ -93,21 +125,22 @@ fn fix_lanes_num(lanes: &mut Vec<Lane>, lanes_num: usize, lane_max_speed: f64) {
     }
 }
 
-fn add_straight_long_way(
+fn add_straight_long_way<R: Rng>(
     board: &mut Board<Option<Intersection>, Option<Road>>,
     settings: &StatelessModelGenerationSettings,
+    rng: &mut R,
 ) {
     let (horizontal_row, horizontal_col) = board.horizontal_roads.shape();
     let (vertical_row, vertical_col) = board.vertical_roads.shape();
     (0..horizontal_row)
-        .filter(|_| rand::random::<f64>() < settings.straight_long_way_proportion)
+        .filter(|_| rng.gen::<f64>() < settings.straight_long_way_proportion)
         .for_each(|row| {
             (0..horizontal_col).for_each(|col| {
                 convert_to_straight_long_way(&mut board.horizontal_roads[(row, col)], settings);
             })
         });
     (0..vertical_col)
-        .filter(|_| rand::random::<f64>() < settings.straight_long_way_proportion)
+        .filter(|_| rng.gen::<f64>() < settings.straight_long_way_proportion)
         .for_each(|col| {
             (0..vertical_row).for_each(|row| {
                 convert_to_straight_long_way(&mut board.vertical_roads[(row, col)], settings);
@@ -0,0 +1,92 @@
+use rand::{seq::SliceRandom, Rng};
+use std::str::FromStr;
+
+use crate::model::{
+    board::{Board, IntersectionIndex},
+    generate::stateless::StatelessModelGenerationSettings,
+    stateless::{Arrival, Intersection, Road, Source},
+};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ArrivalKind {
+    Poisson,
+    Constant,
+    Profile,
+}
+
+impl FromStr for ArrivalKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "poisson" => Ok(ArrivalKind::Poisson),
+            "constant" => Ok(ArrivalKind::Constant),
+            "profile" => Ok(ArrivalKind::Profile),
+            _ => Err(format!("unknown arrival process: {}", s)),
+        }
+    }
+}
+
+/// End intersections on the edge of the board, or all intersections on the
+/// edge if there is no such intersection
+pub fn generate_sinks(board: &Board<Option<Intersection>, Option<Road>>) -> Vec<IntersectionIndex> {
+    let (rows, cols) = board.shape();
+    let on_edge = |(i, j): IntersectionIndex| i == 0 || j == 0 || i + 1 == rows || j + 1 == cols;
+    let ends = board
+        .intersections
+        .enumerate()
+        .filter(|(index, intersection)| {
+            on_edge(*index) && matches!(intersection, Some(Intersection::End { .. }))
+        })
+        .map(|(index, _)| index)
+        .collect::<Vec<_>>();
+    if !ends.is_empty() {
+        return ends;
+    }
+    let edges = board
+        .intersections
+        .enumerate()
+        .filter(|(index, intersection)| on_edge(*index) && intersection.is_some())
+        .map(|(index, _)| index)
+        .collect::<Vec<_>>();
+    if edges.is_empty() {
+        panic!("empty city")
+    }
+    edges
+}
+
+/// Choose sources randomly from sinks
+pub fn generate_sources<R: Rng>(
+    sinks: &[IntersectionIndex],
+    settings: &StatelessModelGenerationSettings,
+    rng: &mut R,
+) -> Vec<Source> {
+    let arrival = match settings.source_arrival {
+        ArrivalKind::Poisson => Arrival::Poisson {
+            rate: settings.source_rate,
+        },
+        ArrivalKind::Constant => Arrival::Constant {
+            rate: settings.source_rate,
+        },
+        ArrivalKind::Profile => Arrival::Profile {
+            rates: settings
+                .source_profile_rates
+                .iter()
+                .enumerate()
+                .map(|(index, rate)| (index as f64 * settings.source_profile_period, *rate))
+                .collect(),
+            cycle: Some(
+                settings.source_profile_rates.len() as f64 * settings.source_profile_period,
+            ),
+        },
+    };
+    let mut sources = sinks
+        .choose_multiple(rng, settings.source_number)
+        .map(|&intersection| Source {
+            intersection,
+            arrival: arrival.clone(),
+        })
+        .collect::<Vec<_>>();
+    sources.sort_by_key(|source| source.intersection);
+    sources
+}
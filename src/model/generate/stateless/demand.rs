@@ -0,0 +1,95 @@
+//! Demand of a scenario loaded from a JSON file
+//!
+//! The file lists sources with their arrival processes, for example a source
+//! with a morning peak:
+//!
+//! ```json
+//! {
+//!   "sources": [
+//!     {
+//!       "intersection": [0, 0],
+//!       "arrival": {
+//!         "Profile": {
+//!           "rates": [[0, 300], [3600, 1200], [7200, 300]],
+//!           "cycle": 86400
+//!         }
+//!       }
+//!     }
+//!   ]
+//! }
+//! ```
+
+use crate::model::{
+    board::IntersectionIndex,
+    stateless::{City, Source},
+};
+use quick_error::quick_error;
+use serde::{Deserialize, Serialize};
+use std::{fs, io, path::Path};
+
+quick_error! {
+    #[derive(Debug)]
+    pub enum DemandError {
+        Io(err: io::Error) {
+            from()
+            display("IO error: {}", err)
+        }
+        Json(err: serde_json::Error) {
+            from()
+            display("JSON error: {}", err)
+        }
+        NotIntersection(index: IntersectionIndex) {
+            display("source at {:?} is not an intersection", index)
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Demand {
+    pub sources: Vec<Source>,
+}
+
+impl Demand {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, DemandError> {
+        Self::from_json(&fs::read_to_string(path)?)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, DemandError> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// Replace sources of `city`, sources must be at existing intersections
+    pub fn apply(self, city: &mut City) -> Result<(), DemandError> {
+        for source in self.sources.iter() {
+            let exists = match city.board.intersections.get(source.intersection) {
+                Some(intersection) => intersection.is_some(),
+                None => false,
+            };
+            if !exists {
+                return Err(DemandError::NotIntersection(source.intersection));
+            }
+        }
+        city.sources = self.sources;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::stateless::Arrival;
+
+    #[test]
+    fn parse_profile() {
+        let demand = Demand::from_json(
+            r#"{"sources": [{"intersection": [1, 2], "arrival": {"Profile": {"rates": [[0, 300], [3600, 1200]], "cycle": null}}}]}"#,
+        )
+        .unwrap();
+        assert_eq!(demand.sources[0].intersection, (1, 2));
+        assert_eq!(demand.sources[0].arrival.rate(4000.0), 1200.0);
+        assert!(matches!(
+            demand.sources[0].arrival,
+            Arrival::Profile { cycle: None, .. }
+        ));
+    }
+}
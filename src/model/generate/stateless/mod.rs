@@ -1,12 +1,24 @@
-use crate::model::stateless::Model;
+use crate::{
+    model::{
+        generate::stateless::{car::DrivingModelKind, city::source::ArrivalKind, demand::Demand},
+        stateless::Model,
+    },
+    util::rng::{self, Stream},
+    Error,
+};
 
+use std::path::PathBuf;
 use structopt::StructOpt;
 
 pub mod car;
 pub mod city;
+pub mod demand;
 
 #[derive(Clone, Debug, StructOpt)]
 pub struct StatelessModelGenerationSettings {
+    /// Seed of all random decisions, a random seed is used if not given
+    #[structopt(name = "seed", long = "seed")]
+    pub seed: Option<u64>,
     #[structopt(
         name = "stateless-model-generation-board-shape-rows",
         default_value = "3",
@@ -39,11 +51,35 @@ pub struct StatelessModelGenerationSettings {
     )]
     pub lane_width: f64,
     #[structopt(
-        name = "stateless-model-generation-initial-car-number",
-        default_value = "20",
-        long = "stateless-model-generation-initial-car-number"
+        name = "stateless-model-generation-car-profile-number",
+        default_value = "100",
+        long = "stateless-model-generation-car-profile-number"
+    )]
+    pub car_profile_number: usize,
+    #[structopt(
+        name = "stateless-model-generation-min-car-length",
+        default_value = "4.0",
+        long = "stateless-model-generation-min-car-length"
+    )]
+    pub min_car_length: f64,
+    #[structopt(
+        name = "stateless-model-generation-max-car-length",
+        default_value = "5.0",
+        long = "stateless-model-generation-max-car-length"
     )]
-    pub initial_car_number: usize,
+    pub max_car_length: f64,
+    #[structopt(
+        name = "stateless-model-generation-min-car-width",
+        default_value = "1.6",
+        long = "stateless-model-generation-min-car-width"
+    )]
+    pub min_car_width: f64,
+    #[structopt(
+        name = "stateless-model-generation-max-car-width",
+        default_value = "1.8",
+        long = "stateless-model-generation-max-car-width"
+    )]
+    pub max_car_width: f64,
     #[structopt(
         name = "stateless-model-generation-min-max-velocity",
         default_value = "100.0",
@@ -116,6 +152,183 @@ pub struct StatelessModelGenerationSettings {
         long = "stateless-model-generation-prediction-time"
     )]
     pub prediction_time: f64,
+    #[structopt(
+        name = "stateless-model-generation-driving-model",
+        default_value = "normal",
+        long = "stateless-model-generation-driving-model",
+        possible_values = &["normal", "intelligent-driver", "idm"]
+    )]
+    pub driving_model: DrivingModelKind,
+    /// Driving model of buses and trucks
+    #[structopt(
+        name = "stateless-model-generation-heavy-driving-model",
+        default_value = "intelligent-driver",
+        long = "stateless-model-generation-heavy-driving-model",
+        possible_values = &["normal", "intelligent-driver", "idm"]
+    )]
+    pub heavy_driving_model: DrivingModelKind,
+    /// Proportions of cars, buses, trucks and motorcycles
+    #[structopt(
+        name = "stateless-model-generation-vehicle-class-mix",
+        default_value = "0.85,0.03,0.07,0.05",
+        long = "stateless-model-generation-vehicle-class-mix",
+        use_delimiter = true
+    )]
+    pub vehicle_class_mix: Vec<f64>,
+    /// Min and max lengths of buses
+    #[structopt(
+        name = "stateless-model-generation-bus-length",
+        default_value = "12,12",
+        long = "stateless-model-generation-bus-length",
+        use_delimiter = true
+    )]
+    pub bus_length: Vec<f64>,
+    /// Min and max widths of buses
+    #[structopt(
+        name = "stateless-model-generation-bus-width",
+        default_value = "2.5,2.5",
+        long = "stateless-model-generation-bus-width",
+        use_delimiter = true
+    )]
+    pub bus_width: Vec<f64>,
+    /// Factors of buses to the ranges of max velocity, max acceleration,
+    /// max break acceleration and timing of cars
+    #[structopt(
+        name = "stateless-model-generation-bus-factors",
+        default_value = "0.7,0.5,0.6,1.5",
+        long = "stateless-model-generation-bus-factors",
+        use_delimiter = true
+    )]
+    pub bus_factors: Vec<f64>,
+    /// Min and max lengths of trucks
+    #[structopt(
+        name = "stateless-model-generation-truck-length",
+        default_value = "8,16",
+        long = "stateless-model-generation-truck-length",
+        use_delimiter = true
+    )]
+    pub truck_length: Vec<f64>,
+    /// Min and max widths of trucks
+    #[structopt(
+        name = "stateless-model-generation-truck-width",
+        default_value = "2.4,2.6",
+        long = "stateless-model-generation-truck-width",
+        use_delimiter = true
+    )]
+    pub truck_width: Vec<f64>,
+    /// Factors of trucks to the ranges of max velocity, max acceleration,
+    /// max break acceleration and timing of cars
+    #[structopt(
+        name = "stateless-model-generation-truck-factors",
+        default_value = "0.65,0.4,0.5,1.5",
+        long = "stateless-model-generation-truck-factors",
+        use_delimiter = true
+    )]
+    pub truck_factors: Vec<f64>,
+    /// Min and max lengths of motorcycles
+    #[structopt(
+        name = "stateless-model-generation-motorcycle-length",
+        default_value = "2,2.5",
+        long = "stateless-model-generation-motorcycle-length",
+        use_delimiter = true
+    )]
+    pub motorcycle_length: Vec<f64>,
+    /// Min and max widths of motorcycles
+    #[structopt(
+        name = "stateless-model-generation-motorcycle-width",
+        default_value = "0.8,0.9",
+        long = "stateless-model-generation-motorcycle-width",
+        use_delimiter = true
+    )]
+    pub motorcycle_width: Vec<f64>,
+    /// Factors of motorcycles to the ranges of max velocity, max acceleration,
+    /// max break acceleration and timing of cars
+    #[structopt(
+        name = "stateless-model-generation-motorcycle-factors",
+        default_value = "1.1,1.5,0.9,0.7",
+        long = "stateless-model-generation-motorcycle-factors",
+        use_delimiter = true
+    )]
+    pub motorcycle_factors: Vec<f64>,
+    #[structopt(
+        name = "stateless-model-generation-min-time-headway",
+        default_value = "1.0",
+        long = "stateless-model-generation-min-time-headway"
+    )]
+    pub min_time_headway: f64,
+    #[structopt(
+        name = "stateless-model-generation-max-time-headway",
+        default_value = "1.5",
+        long = "stateless-model-generation-max-time-headway"
+    )]
+    pub max_time_headway: f64,
+    #[structopt(
+        name = "stateless-model-generation-min-gap",
+        default_value = "2.0",
+        long = "stateless-model-generation-min-gap"
+    )]
+    pub min_gap: f64,
+    #[structopt(
+        name = "stateless-model-generation-comfortable-deceleration",
+        default_value = "10.0",
+        long = "stateless-model-generation-comfortable-deceleration"
+    )]
+    pub comfortable_deceleration: f64,
+    #[structopt(
+        name = "stateless-model-generation-acceleration-exponent",
+        default_value = "4.0",
+        long = "stateless-model-generation-acceleration-exponent"
+    )]
+    pub acceleration_exponent: f64,
+    #[structopt(
+        name = "stateless-model-generation-min-politeness",
+        default_value = "0.1",
+        long = "stateless-model-generation-min-politeness"
+    )]
+    pub min_politeness: f64,
+    #[structopt(
+        name = "stateless-model-generation-max-politeness",
+        default_value = "0.5",
+        long = "stateless-model-generation-max-politeness"
+    )]
+    pub max_politeness: f64,
+    #[structopt(
+        name = "stateless-model-generation-lane-change-threshold",
+        default_value = "1.0",
+        long = "stateless-model-generation-lane-change-threshold"
+    )]
+    pub lane_change_threshold: f64,
+    #[structopt(
+        name = "stateless-model-generation-lane-change-bias",
+        default_value = "0.3",
+        long = "stateless-model-generation-lane-change-bias"
+    )]
+    pub lane_change_bias: f64,
+    #[structopt(
+        name = "stateless-model-generation-lane-change-safe-break-acceleration",
+        default_value = "20.0",
+        long = "stateless-model-generation-lane-change-safe-break-acceleration"
+    )]
+    pub lane_change_safe_break_acceleration: f64,
+    #[structopt(
+        name = "stateless-model-generation-min-critical-gap",
+        default_value = "1.0",
+        long = "stateless-model-generation-min-critical-gap"
+    )]
+    pub min_critical_gap: f64,
+    #[structopt(
+        name = "stateless-model-generation-max-critical-gap",
+        default_value = "2.0",
+        long = "stateless-model-generation-max-critical-gap"
+    )]
+    pub max_critical_gap: f64,
+    /// Proportion of drivers re-planning routes with live travel times
+    #[structopt(
+        name = "stateless-model-generation-informed-proportion",
+        default_value = "0.3",
+        long = "stateless-model-generation-informed-proportion"
+    )]
+    pub informed_proportion: f64,
     #[structopt(
         name = "stateless-model-generation-time-out",
         default_value = "10.0",
@@ -128,12 +341,30 @@ pub struct StatelessModelGenerationSettings {
         long = "stateless-model-generation-intersection-max-speed"
     )]
     pub intersection_max_speed: f64,
+    /// Let left turns share the signal phase with opposing straight traffic
+    #[structopt(
+        name = "stateless-model-generation-permissive-left-turn",
+        long = "stateless-model-generation-permissive-left-turn"
+    )]
+    pub permissive_left_turn: bool,
     #[structopt(
         name = "stateless-model-generation-lane-max-speed",
         default_value = "40.0",
         long = "stateless-model-generation-lane-max-speed"
     )]
     pub lane_max_speed: f64,
+    #[structopt(
+        name = "stateless-model-generation-speed-zone-proportion",
+        default_value = "0.2",
+        long = "stateless-model-generation-speed-zone-proportion"
+    )]
+    pub speed_zone_proportion: f64,
+    #[structopt(
+        name = "stateless-model-generation-speed-zone-max-speed",
+        default_value = "20.0",
+        long = "stateless-model-generation-speed-zone-max-speed"
+    )]
+    pub speed_zone_max_speed: f64,
     #[structopt(
         name = "stateless-model-generation-straight-long-way-proportion",
         default_value = "0.5",
@@ -177,11 +408,108 @@ pub struct StatelessModelGenerationSettings {
         long = "stateless-model-generation-car-out-min-distance"
     )]
     pub car_out_min_distance: f64,
+    #[structopt(
+        name = "stateless-model-generation-source-number",
+        default_value = "4",
+        long = "stateless-model-generation-source-number"
+    )]
+    pub source_number: usize,
+    #[structopt(
+        name = "stateless-model-generation-source-arrival",
+        default_value = "poisson",
+        long = "stateless-model-generation-source-arrival",
+        possible_values = &["poisson", "constant", "profile"]
+    )]
+    pub source_arrival: ArrivalKind,
+    /// Arrival rate of each source in cars per hour
+    #[structopt(
+        name = "stateless-model-generation-source-rate",
+        default_value = "600",
+        long = "stateless-model-generation-source-rate"
+    )]
+    pub source_rate: f64,
+    /// Time each rate of the arrival profile applies in seconds
+    #[structopt(
+        name = "stateless-model-generation-source-profile-period",
+        default_value = "900",
+        long = "stateless-model-generation-source-profile-period"
+    )]
+    pub source_profile_period: f64,
+    /// Rates of the arrival profile in cars per hour
+    #[structopt(
+        name = "stateless-model-generation-source-profile-rates",
+        default_value = "300,900,300",
+        long = "stateless-model-generation-source-profile-rates",
+        use_delimiter = true
+    )]
+    pub source_profile_rates: Vec<f64>,
+    /// JSON file of sources replacing the generated ones
+    #[structopt(
+        name = "stateless-model-generation-demand-file",
+        long = "stateless-model-generation-demand-file",
+        parse(from_os_str)
+    )]
+    pub demand_file: Option<PathBuf>,
 }
 
-pub fn generate_stateless_model(settings: StatelessModelGenerationSettings) -> Model {
-    Model {
-        city: city::generate_city(&settings),
-        cars: car::generate_cars(&settings),
+/// Reject settings generating lanes or intersections cars can not pass
+pub fn validate_generation_settings(
+    settings: &StatelessModelGenerationSettings,
+) -> Result<(), Error> {
+    let speeds = [
+        ("intersection max speed", settings.intersection_max_speed),
+        ("lane max speed", settings.lane_max_speed),
+        ("speed zone max speed", settings.speed_zone_max_speed),
+    ];
+    for (name, speed) in speeds.iter() {
+        if speed.is_nan() || *speed <= 0.0 {
+            return Err(Error::InvalidSettings(format!(
+                "{} of {}, expected a positive speed",
+                name, speed
+            )));
+        }
+    }
+    Ok(())
+}
+
+pub fn generate_stateless_model(
+    settings: StatelessModelGenerationSettings,
+) -> Result<Model, Error> {
+    validate_generation_settings(&settings)?;
+    let seed = settings.seed.unwrap_or_else(rand::random);
+    log::info!("seed: {}", seed);
+    let mut city_rng = rng::stream_rng(seed, Stream::City, 0, 0);
+    let mut city = city::generate_city(&settings, &mut city_rng);
+    if let Some(path) = &settings.demand_file {
+        Demand::load(path)?.apply(&mut city)?;
+    }
+    Ok(Model {
+        city,
+        cars: car::generate_cars(&settings, seed)?,
+        seed,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_speeds() {
+        for option in &[
+            "--stateless-model-generation-intersection-max-speed",
+            "--stateless-model-generation-lane-max-speed",
+            "--stateless-model-generation-speed-zone-max-speed",
+        ] {
+            for speed in &["0", "-1"] {
+                let option = format!("{}={}", option, speed);
+                let settings =
+                    StatelessModelGenerationSettings::from_iter(&["test", "--seed", "2", &option]);
+                assert!(matches!(
+                    generate_stateless_model(settings),
+                    Err(Error::InvalidSettings(_))
+                ));
+            }
+        }
     }
 }
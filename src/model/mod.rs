@@ -1,4 +1,5 @@
 pub mod board;
+pub mod city_file;
 pub mod common;
 pub mod generate;
 pub mod stateful;
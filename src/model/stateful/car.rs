@@ -3,12 +3,25 @@ use crate::model::{
     common::{AbsoluteDirection, AxisDirection, LaneDirection, LaneIndex, RelativeDirection},
 };
 use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Car {
     pub location: Location,
     pub velocity: f64,
     pub acceleration: f64,
+    pub trip: Trip,
+    /// Index of the driver and vehicle profile in `stateless::Model::cars`
+    pub profile: usize,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Trip {
+    pub origin: IntersectionIndex,
+    pub destination: IntersectionIndex,
+    /// Turns at the end of the current road and the following roads, empty if
+    /// the current road leads to the destination
+    pub route: VecDeque<RelativeDirection>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -27,6 +40,7 @@ pub enum Location {
         lane_direction: LaneDirection,
         from_lane_index: LaneIndex,
         to_lane_index: LaneIndex,
+        about_to_turn: RelativeDirection,
         position: f64,
         /// Position in lane changing.
         lane_changed_proportion: f64,
@@ -41,3 +55,152 @@ pub enum Location {
         position: f64,
     },
 }
+
+impl Location {
+    /// Return the position on the lane, `None` if the car is in an intersection
+    pub fn lane_position(&self) -> Option<f64> {
+        match self {
+            Location::OnLane { position, .. } => Some(*position),
+            Location::ChangingLane { position, .. } => Some(*position),
+            Location::InIntersection { .. } => None,
+        }
+    }
+
+    /// Return the location between `self` and the later location `next`, or
+    /// `next` if the car left the lane or the intersection of `self`.
+    pub fn interpolate(&self, next: &Location, alpha: f64) -> Location {
+        use Location::*;
+        let lerp = |from: f64, to: f64| from + (to - from) * alpha;
+        let mut location = next.clone();
+        match (self, &mut location) {
+            (
+                OnLane {
+                    road_direction,
+                    road_index,
+                    lane_direction,
+                    lane_index,
+                    position,
+                    ..
+                },
+                OnLane {
+                    road_direction: next_road_direction,
+                    road_index: next_road_index,
+                    lane_direction: next_lane_direction,
+                    lane_index: next_lane_index,
+                    position: next_position,
+                    ..
+                },
+            ) if (road_direction, road_index, lane_direction, lane_index)
+                == (
+                    next_road_direction,
+                    next_road_index,
+                    next_lane_direction,
+                    next_lane_index,
+                ) =>
+            {
+                *next_position = lerp(*position, *next_position)
+            }
+            (
+                ChangingLane {
+                    road_direction,
+                    road_index,
+                    lane_direction,
+                    from_lane_index,
+                    to_lane_index,
+                    position,
+                    lane_changed_proportion,
+                    ..
+                },
+                ChangingLane {
+                    road_direction: next_road_direction,
+                    road_index: next_road_index,
+                    lane_direction: next_lane_direction,
+                    from_lane_index: next_from_lane_index,
+                    to_lane_index: next_to_lane_index,
+                    position: next_position,
+                    lane_changed_proportion: next_lane_changed_proportion,
+                    ..
+                },
+            ) if (
+                road_direction,
+                road_index,
+                lane_direction,
+                from_lane_index,
+                to_lane_index,
+            ) == (
+                next_road_direction,
+                next_road_index,
+                next_lane_direction,
+                next_from_lane_index,
+                next_to_lane_index,
+            ) =>
+            {
+                *next_position = lerp(*position, *next_position);
+                *next_lane_changed_proportion =
+                    lerp(*lane_changed_proportion, *next_lane_changed_proportion);
+            }
+            (
+                InIntersection {
+                    intersection_index,
+                    from_direction,
+                    from_lane_index,
+                    to_direction,
+                    to_lane_index,
+                    position,
+                    ..
+                },
+                InIntersection {
+                    intersection_index: next_intersection_index,
+                    from_direction: next_from_direction,
+                    from_lane_index: next_from_lane_index,
+                    to_direction: next_to_direction,
+                    to_lane_index: next_to_lane_index,
+                    position: next_position,
+                    ..
+                },
+            ) if (
+                intersection_index,
+                from_direction,
+                from_lane_index,
+                to_direction,
+                to_lane_index,
+            ) == (
+                next_intersection_index,
+                next_from_direction,
+                next_from_lane_index,
+                next_to_direction,
+                next_to_lane_index,
+            ) =>
+            {
+                *next_position = lerp(*position, *next_position)
+            }
+            _ => (),
+        }
+        location
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn on_lane(lane_index: LaneIndex, position: f64) -> Location {
+        Location::OnLane {
+            road_direction: AxisDirection::Horizontal,
+            road_index: (0, 0),
+            lane_direction: LaneDirection::LowToHigh,
+            lane_index,
+            about_to_turn: RelativeDirection::Front,
+            position,
+        }
+    }
+
+    #[test]
+    fn interpolate() {
+        let location = on_lane(0, 10.0).interpolate(&on_lane(0, 20.0), 0.25);
+        assert_eq!(location.lane_position(), Some(12.5));
+        // another lane
+        let location = on_lane(0, 10.0).interpolate(&on_lane(1, 20.0), 0.25);
+        assert_eq!(location.lane_position(), Some(20.0));
+    }
+}
@@ -29,6 +29,26 @@ pub enum SwitchState {
 }
 
 impl Intersection {
+    /// Turn rules of signals in effect, `None` if the intersection has no
+    /// signals
+    pub fn current(&self) -> Option<&Around<TurnRule>> {
+        match self {
+            Intersection::Crossroad { current, .. } | Intersection::TJunction { current, .. } => {
+                Some(current)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn current_mut(&mut self) -> Option<&mut Around<TurnRule>> {
+        match self {
+            Intersection::Crossroad { current, .. } | Intersection::TJunction { current, .. } => {
+                Some(current)
+            }
+            _ => None,
+        }
+    }
+
     pub fn update_current(&mut self, stateless: &stateless::Intersection) {
         match (self, stateless) {
             (
@@ -1,21 +1,29 @@
 //! Module `stateful` is the dynamic part of the simulation
 
-use crate::model::board::Board;
+use crate::{model::board::Board, util::pool::Pool};
 use serde::{Deserialize, Serialize};
 
 pub mod car;
 pub mod intersection;
+pub mod source;
 
 pub use car::Car;
 pub use intersection::Intersection;
+pub use source::Source;
 
 #[derive(Serialize, Deserialize, Clone, Debug, Default)]
 pub struct City {
     pub board: Board<Option<Intersection>, ()>,
+    /// States of sources in the order of `stateless::City::sources`
+    pub sources: Vec<Source>,
+    /// Simulated time
+    pub time: f64,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, Default)]
 pub struct Model {
     pub city: City,
-    pub cars: Vec<Option<Car>>,
+    pub cars: Pool<Car>,
+    /// Number of updated steps of cars, used as the counter of random streams
+    pub step: u64,
 }
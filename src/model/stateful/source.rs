@@ -0,0 +1,24 @@
+use crate::model::stateless::Arrival;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct Source {
+    /// Cars arrived but not spawned yet
+    pub waiting: usize,
+    /// Remaining work of unit rate to the next arrival
+    pub remaining: f64,
+    /// Number of arrivals so far, used as the counter of random streams
+    pub arrivals: u64,
+}
+
+impl Source {
+    /// A source waiting for the work to its first arrival, so no car arrives
+    /// at the start
+    pub fn new(arrival: &Arrival, seed: u64, source_index: usize) -> Self {
+        Source {
+            waiting: 0,
+            remaining: arrival.work(seed, source_index, 0),
+            arrivals: 0,
+        }
+    }
+}
@@ -2,21 +2,96 @@ use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Car {
+    pub class: VehicleClass,
+    /// Length of the car, the position of a car on a lane is the position of
+    /// its front bumper
+    pub length: f64,
+    pub width: f64,
     pub max_velocity: f64,
     pub max_acceleration: f64,
     pub max_break_acceleration: f64,
     pub lane_change_time: f64,
+    /// Min time to the arrival of an opposing car for a permissive left turn
+    pub critical_gap: f64,
     pub driving_model: DrivingModel,
+    pub lane_change_model: LaneChangeModel,
+    /// Whether the driver re-plans the route with live travel times
+    pub informed: bool,
 }
 
+/// Class of a vehicle
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Eq, PartialEq)]
+pub enum VehicleClass {
+    Car,
+    Bus,
+    Truck,
+    Motorcycle,
+}
+
+impl VehicleClass {
+    pub fn classes() -> std::slice::Iter<'static, VehicleClass> {
+        use VehicleClass::*;
+        static CLASSES: [VehicleClass; 4] = [Car, Bus, Truck, Motorcycle];
+        CLASSES.iter()
+    }
+}
+
+/// Car following model of a driver.
+///
+/// Built-in models are listed as variants, other models can be registered by
+/// name and referred with `Custom`.
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub enum DrivingModel {
-    Normal {
-        /// Min cushion
-        min_cushion: f64,
-        /// Cushion speed factor
-        cushion_velocity_factor: f64,
-        /// Prediction time
-        prediction_time: f64,
+    Normal(NormalDrivingModel),
+    IntelligentDriver(IntelligentDriverModel),
+    Custom(CustomDrivingModel),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct NormalDrivingModel {
+    /// Min cushion
+    pub min_cushion: f64,
+    /// Cushion speed factor
+    pub cushion_velocity_factor: f64,
+    /// Prediction time
+    pub prediction_time: f64,
+}
+
+/// Intelligent Driver Model
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct IntelligentDriverModel {
+    /// Desired velocity on a free road
+    pub desired_velocity: f64,
+    /// Desired time headway to the front object
+    pub time_headway: f64,
+    /// Min gap to the front object
+    pub min_gap: f64,
+    /// Max acceleration
+    pub max_acceleration: f64,
+    /// Comfortable deceleration
+    pub comfortable_deceleration: f64,
+    /// Acceleration exponent
+    pub acceleration_exponent: f64,
+}
+
+/// A driving model registered by name
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CustomDrivingModel {
+    pub name: String,
+    pub parameters: Vec<f64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum LaneChangeModel {
+    /// Minimizing Overall Braking Induced by Lane changes
+    Mobil {
+        /// Weight of accelerations of other cars
+        politeness: f64,
+        /// Min acceleration advantage to change lane
+        threshold: f64,
+        /// Acceleration bias to keep right
+        bias: f64,
+        /// Max break acceleration a new follower can be forced to
+        safe_break_acceleration: f64,
     },
 }
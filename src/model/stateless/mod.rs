@@ -3,6 +3,7 @@
 pub mod car;
 pub mod intersection;
 pub mod road;
+pub mod source;
 
 use crate::model::{
     board::{Board, IntersectionIndex, RoadIndex},
@@ -10,17 +11,22 @@ use crate::model::{
         AbsoluteDirection, AxisDirection, Geometry, InOutDirection, LaneDirection, LaneIndex,
         Position,
     },
+    stateful,
 };
 pub use car::Car;
 pub use intersection::Intersection;
 pub use road::{Lane, Road};
+pub use source::{Arrival, Source};
 
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize, Clone, Debug, Default)]
 pub struct City {
     pub board: Board<Option<Intersection>, Option<Road>>,
-    pub car_out_intersection: IntersectionIndex,
+    /// Entry points of cars
+    pub sources: Vec<Source>,
+    /// Exit points of cars, the destinations of trips
+    pub sinks: Vec<IntersectionIndex>,
     pub car_out_min_distance: f64,
     pub lane_width: f64,
     pub horizontal_road_length: Vec<f64>,
@@ -32,7 +38,17 @@ pub struct City {
 #[derive(Serialize, Deserialize, Clone, Debug, Default)]
 pub struct Model {
     pub city: City,
+    /// Profiles of drivers and vehicles, each new car draws one
     pub cars: Vec<Car>,
+    /// Seed of random streams
+    pub seed: u64,
+}
+
+impl Model {
+    /// Profile of a car
+    pub fn car(&self, car: &stateful::Car) -> &Car {
+        &self.cars[car.profile]
+    }
 }
 
 impl City {
@@ -96,6 +112,22 @@ impl City {
         }
     }
 
+    /// The lowest max speed of `lane_indices` on a road
+    pub fn lane_speed_limit(
+        &self,
+        (road_direction, road_index, lane_direction): (AxisDirection, RoadIndex, LaneDirection),
+        lane_indices: &[LaneIndex],
+    ) -> f64 {
+        let lanes = self.board.get_roads(road_direction)[road_index]
+            .as_ref()
+            .unwrap()
+            .lanes_to_direction(lane_direction);
+        lane_indices
+            .iter()
+            .map(|lane_index| lanes[*lane_index].max_speed)
+            .fold(f64::INFINITY, f64::min)
+    }
+
     pub fn intersection_geometry(&self, (i, j): IntersectionIndex) -> Geometry {
         Geometry {
             width: self.intersection_width[j],
@@ -140,15 +172,16 @@ impl City {
         Some(position)
     }
 
-    /// Return the join point relative to intersection center
-    pub fn intersection_path_total_length(
+    /// Return the start and end point of a path in an intersection relative
+    /// to intersection center
+    pub fn intersection_path(
         &self,
         intersection_index: IntersectionIndex,
         from_direction: AbsoluteDirection,
         from_lane_index: LaneIndex,
         to_direction: AbsoluteDirection,
         to_lane_index: LaneIndex,
-    ) -> Option<f64> {
+    ) -> Option<(Position, Position)> {
         let from_position = self.intersection_road_join_position(
             intersection_index,
             from_direction,
@@ -161,6 +194,24 @@ impl City {
             InOutDirection::Out,
             to_lane_index,
         )?;
+        Some((from_position, to_position))
+    }
+
+    pub fn intersection_path_total_length(
+        &self,
+        intersection_index: IntersectionIndex,
+        from_direction: AbsoluteDirection,
+        from_lane_index: LaneIndex,
+        to_direction: AbsoluteDirection,
+        to_lane_index: LaneIndex,
+    ) -> Option<f64> {
+        let (from_position, to_position) = self.intersection_path(
+            intersection_index,
+            from_direction,
+            from_lane_index,
+            to_direction,
+            to_lane_index,
+        )?;
         Some(from_position.distance(to_position))
     }
 
@@ -187,7 +238,8 @@ mod tests {
     fn example_city() -> City {
         City {
             board: Board::with_shape(None, None, (3, 3)),
-            car_out_intersection: (0, 0),
+            sources: vec![],
+            sinks: vec![],
             car_out_min_distance: 8.0,
             lane_width: 3.5,
             horizontal_road_length: vec![500.0, 500.0],
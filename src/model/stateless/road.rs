@@ -29,6 +29,13 @@ impl Road {
             LaneDirection::LowToHigh => &mut self.lane_to_high,
         }
     }
+
+    /// Union of the direction rules of all lanes to `lane_direction`
+    pub fn turn_rule_to_direction(&self, lane_direction: LaneDirection) -> TurnRule {
+        self.lanes_to_direction(lane_direction)
+            .iter()
+            .fold(TurnRule::empty(), |rule, lane| rule | lane.direction_rule)
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -0,0 +1,89 @@
+use crate::{
+    model::board::IntersectionIndex,
+    util::rng::{self, Stream},
+};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+/// An entry point of cars at an intersection
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Source {
+    pub intersection: IntersectionIndex,
+    pub arrival: Arrival,
+}
+
+/// Arrival process of cars at a source, rates are in cars per hour
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum Arrival {
+    /// Poisson arrivals with a constant rate
+    Poisson { rate: f64 },
+    /// Evenly spaced arrivals
+    Constant { rate: f64 },
+    /// Poisson arrivals with piecewise constant rates, `rates` are pairs of
+    /// start time in seconds and rate sorted by time, each rate applies until
+    /// the next start time. No car arrives before the first start time. The
+    /// profile repeats every `cycle` seconds if given.
+    Profile {
+        rates: Vec<(f64, f64)>,
+        cycle: Option<f64>,
+    },
+}
+
+impl Arrival {
+    /// Arrival rate at `time`
+    pub fn rate(&self, time: f64) -> f64 {
+        match self {
+            Arrival::Poisson { rate } | Arrival::Constant { rate } => *rate,
+            Arrival::Profile { rates, cycle } => {
+                let time = match cycle {
+                    Some(cycle) if *cycle > 0.0 => time.rem_euclid(*cycle),
+                    _ => time,
+                };
+                rates
+                    .iter()
+                    .take_while(|(start, _)| *start <= time)
+                    .last()
+                    .map_or(0.0, |(_, rate)| *rate)
+            }
+        }
+    }
+
+    /// Whether intervals between arrivals are random
+    pub fn is_random(&self) -> bool {
+        !matches!(self, Arrival::Constant { .. })
+    }
+
+    /// Work of unit rate to an arrival, drawn from the unit exponential
+    /// distribution for random arrivals with the `draw` counter of the
+    /// random stream of the source, or 1 for constant arrivals
+    pub fn work(&self, seed: u64, source_index: usize, draw: u64) -> f64 {
+        if self.is_random() {
+            let mut rng = rng::stream_rng(seed, Stream::Source, source_index as u64, draw);
+            -(1.0 - rng.gen::<f64>()).ln()
+        } else {
+            1.0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profile_rate() {
+        let arrival = Arrival::Profile {
+            rates: vec![(10.0, 100.0), (60.0, 200.0)],
+            cycle: Some(120.0),
+        };
+        assert_eq!(arrival.rate(0.0), 0.0);
+        assert_eq!(arrival.rate(10.0), 100.0);
+        assert_eq!(arrival.rate(90.0), 200.0);
+        assert_eq!(arrival.rate(130.0), 100.0);
+        let arrival = Arrival::Profile {
+            rates: vec![(0.0, 100.0), (60.0, 200.0)],
+            cycle: None,
+        };
+        assert_eq!(arrival.rate(1000.0), 200.0);
+    }
+}
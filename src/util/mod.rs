@@ -1,2 +1,4 @@
 pub mod dump;
 pub mod matrix;
+pub mod pool;
+pub mod rng;
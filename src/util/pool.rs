@@ -0,0 +1,146 @@
+use serde::{Deserialize, Serialize};
+use std::ops::{Index, IndexMut};
+
+pub type PoolId = usize;
+
+/// A growable store of values with stable IDs.
+///
+/// IDs are assigned in increasing order and never reused, entries are kept
+/// sorted by ID, so a range of positions is a range of IDs.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct Pool<T> {
+    entries: Vec<(PoolId, T)>,
+    next_id: PoolId,
+}
+
+impl<T> Default for Pool<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Index<PoolId> for Pool<T> {
+    type Output = T;
+
+    fn index(&self, id: PoolId) -> &Self::Output {
+        self.get(id).unwrap()
+    }
+}
+
+impl<T> IndexMut<PoolId> for Pool<T> {
+    fn index_mut(&mut self, id: PoolId) -> &mut Self::Output {
+        self.get_mut(id).unwrap()
+    }
+}
+
+impl<T> Pool<T> {
+    pub fn new() -> Self {
+        Pool {
+            entries: Vec::new(),
+            next_id: 0,
+        }
+    }
+
+    /// Create a pool from entries with distinct IDs less than `next_id`
+    pub fn from_entries(mut entries: Vec<(PoolId, T)>, next_id: PoolId) -> Self {
+        entries.sort_by_key(|(id, _)| *id);
+        debug_assert!(entries.windows(2).all(|w| w[0].0 < w[1].0));
+        debug_assert!(match entries.last() {
+            Some((id, _)) => *id < next_id,
+            None => true,
+        });
+        Pool { entries, next_id }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The ID of the next inserted value
+    pub fn next_id(&self) -> PoolId {
+        self.next_id
+    }
+
+    fn position(&self, id: PoolId) -> Option<usize> {
+        self.entries.binary_search_by_key(&id, |(id, _)| *id).ok()
+    }
+
+    pub fn get(&self, id: PoolId) -> Option<&T> {
+        let position = self.position(id)?;
+        Some(&self.entries[position].1)
+    }
+
+    pub fn get_mut(&mut self, id: PoolId) -> Option<&mut T> {
+        let position = self.position(id)?;
+        Some(&mut self.entries[position].1)
+    }
+
+    pub fn contains(&self, id: PoolId) -> bool {
+        self.position(id).is_some()
+    }
+
+    pub fn insert(&mut self, value: T) -> PoolId {
+        let id = self.next_id;
+        self.entries.push((id, value));
+        self.next_id += 1;
+        id
+    }
+
+    pub fn remove(&mut self, id: PoolId) -> Option<T> {
+        let position = self.position(id)?;
+        Some(self.entries.remove(position).1)
+    }
+
+    /// Keep only the values `f` returns `true` for
+    pub fn retain<F: FnMut(PoolId, &T) -> bool>(&mut self, mut f: F) {
+        self.entries.retain(|(id, value)| f(*id, value));
+    }
+
+    /// Entries sorted by ID
+    pub fn entries(&self) -> &[(PoolId, T)] {
+        &self.entries
+    }
+
+    pub fn into_entries(self) -> Vec<(PoolId, T)> {
+        self.entries
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (PoolId, &T)> {
+        self.entries.iter().map(|(id, value)| (*id, value))
+    }
+
+    pub fn ids(&self) -> impl Iterator<Item = PoolId> + '_ {
+        self.entries.iter().map(|(id, _)| *id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stable_ids() {
+        let mut pool = Pool::new();
+        let a = pool.insert('a');
+        let b = pool.insert('b');
+        let c = pool.insert('c');
+        assert_eq!(pool.remove(b), Some('b'));
+        let d = pool.insert('d');
+        assert_eq!((a, c, d), (0, 2, 3));
+        assert_eq!(pool.ids().collect::<Vec<_>>(), vec![0, 2, 3]);
+        assert_eq!(pool[c], 'c');
+        assert_eq!(pool.get(b), None);
+        assert_eq!(pool.len(), 3);
+    }
+
+    #[test]
+    fn from_entries() {
+        let pool = Pool::from_entries(vec![(5, 'b'), (1, 'a')], 7);
+        assert_eq!(pool.ids().collect::<Vec<_>>(), vec![1, 5]);
+        assert_eq!(pool.next_id(), 7);
+    }
+}
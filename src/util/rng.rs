@@ -0,0 +1,63 @@
+//! Counter-based random number streams
+//!
+//! Every random decision draws from a stream identified by the simulation
+//! seed, a purpose, an entity (e.g. a car index) and a counter (e.g. the
+//! simulation step). The random numbers only depend on these identifiers, so
+//! results do not depend on which process makes the decision.
+
+use rand::{rngs::StdRng, SeedableRng};
+
+/// Purposes of random streams
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Stream {
+    City = 1,
+    Car = 2,
+    CarUpdate = 3,
+    Source = 4,
+}
+
+/// SplitMix64 finalizer
+pub fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Create the random number generator of a stream
+pub fn stream_rng(seed: u64, stream: Stream, entity: u64, counter: u64) -> StdRng {
+    let key = [stream as u64, entity, counter]
+        .iter()
+        .fold(splitmix64(seed), |state, x| splitmix64(state ^ x));
+    StdRng::seed_from_u64(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+
+    #[test]
+    fn same_stream_same_numbers() {
+        let a: Vec<u64> = stream_rng(42, Stream::CarUpdate, 3, 7)
+            .sample_iter(rand::distributions::Standard)
+            .take(4)
+            .collect();
+        let b: Vec<u64> = stream_rng(42, Stream::CarUpdate, 3, 7)
+            .sample_iter(rand::distributions::Standard)
+            .take(4)
+            .collect();
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn different_streams_different_numbers() {
+        let first =
+            |seed, stream, entity, counter| stream_rng(seed, stream, entity, counter).gen::<u64>();
+        let base = first(42, Stream::CarUpdate, 3, 7);
+        assert_ne!(base, first(43, Stream::CarUpdate, 3, 7));
+        assert_ne!(base, first(42, Stream::Car, 3, 7));
+        assert_ne!(base, first(42, Stream::CarUpdate, 4, 7));
+        assert_ne!(base, first(42, Stream::CarUpdate, 3, 8));
+    }
+}
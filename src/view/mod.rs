@@ -6,8 +6,10 @@ use crate::{
             AbsoluteDirection, AxisDirection, Geometry, InOutDirection, LaneDirection, LaneIndex,
             Position, RelativeDirection, TurnRule,
         },
-        stateful, stateless,
+        stateful,
+        stateless::{self, car::VehicleClass},
     },
+    util::pool::Pool,
 };
 use piston_window::{
     context::Context,
@@ -17,6 +19,14 @@ use piston_window::{
 };
 use structopt::StructOpt;
 
+/// Cars of the previous step and the elapsed proportion of the next step, to
+/// draw cars between steps
+#[derive(Clone, Copy, Debug)]
+pub struct Interpolation<'a> {
+    pub previous: &'a Pool<stateful::Car>,
+    pub alpha: f64,
+}
+
 #[derive(Clone, Debug)]
 pub struct View {
     pub settings: ViewSettings,
@@ -88,17 +98,26 @@ pub struct ViewSettings {
     )]
     pub car_color: Color,
     #[structopt(
-        name = "view-car-length",
-        long = "view-car-length",
-        default_value = "4.5"
+        name = "view-bus-color",
+        long = "view-bus-color",
+        default_value = "ffcc00",
+        parse(from_str = piston_window::color::hex)
+    )]
+    pub bus_color: Color,
+    #[structopt(
+        name = "view-truck-color",
+        long = "view-truck-color",
+        default_value = "3366ff",
+        parse(from_str = piston_window::color::hex)
     )]
-    pub car_length: f64,
+    pub truck_color: Color,
     #[structopt(
-        name = "view-car-width",
-        long = "view-car-width",
-        default_value = "1.7"
+        name = "view-motorcycle-color",
+        long = "view-motorcycle-color",
+        default_value = "00e6e6",
+        parse(from_str = piston_window::color::hex)
     )]
-    pub car_width: f64,
+    pub motorcycle_color: Color,
 }
 
 impl View {
@@ -107,6 +126,7 @@ impl View {
         info: &Info,
         stateless_model: &stateless::Model,
         stateful_model: &stateful::Model,
+        interpolation: Option<Interpolation>,
         context: Context,
         g2d: &mut G2d,
     ) {
@@ -188,18 +208,30 @@ impl View {
             }
         }
 
-        for (stateless_car, stateful_car) in
-            stateless_model.cars.iter().zip(stateful_model.cars.iter())
-        {
-            if let Some(stateful_car) = stateful_car {
-                self.draw_car(
-                    stateless_car,
-                    stateful_car,
-                    &stateless_model.city,
-                    model_context.transform,
-                    g2d,
-                );
-            }
+        for (car_index, stateful_car) in stateful_model.cars.iter() {
+            let previous = interpolation.and_then(|i| {
+                i.previous
+                    .get(car_index)
+                    .map(|previous| (previous, i.alpha))
+            });
+            let interpolated;
+            let car = match previous {
+                Some((previous, alpha)) => {
+                    interpolated = stateful::Car {
+                        location: previous.location.interpolate(&stateful_car.location, alpha),
+                        ..stateful_car.clone()
+                    };
+                    &interpolated
+                }
+                None => stateful_car,
+            };
+            self.draw_car(
+                stateless_model.car(stateful_car),
+                car,
+                &stateless_model.city,
+                model_context.transform,
+                g2d,
+            );
         }
     }
 
@@ -469,7 +501,7 @@ impl View {
 
     pub fn draw_car(
         &self,
-        _stateless: &stateless::Car,
+        stateless: &stateless::Car,
         stateful: &stateful::Car,
         city: &stateless::City,
         transform: Matrix2d,
@@ -488,6 +520,7 @@ impl View {
                 let length = city.road_length(road_direction, road_index);
                 let x = -length / 2.0 + position;
                 self.draw_car_only(
+                    stateless,
                     self.transform_to_lane_center(
                         transform,
                         city,
@@ -518,17 +551,21 @@ impl View {
                 to_lane_index,
                 position,
                 lane_changed_proportion,
+                ..
             } => {
                 let length = city.road_length(road_direction, road_index);
                 let x = -length / 2.0 + position;
+                let road = city
+                    .board
+                    .get_road(road_direction, road_index)
+                    .unwrap()
+                    .as_ref()
+                    .unwrap();
                 let lane_changed_offset = lane_changed_proportion
-                    * city.lane_width
-                    * (to_lane_index - from_lane_index) as f64
-                    * match lane_direction {
-                        LaneDirection::HighToLow => 1.0,
-                        LaneDirection::LowToHigh => -1.0,
-                    };
+                    * (city.lane_center_offset(road, lane_direction, to_lane_index)
+                        - city.lane_center_offset(road, lane_direction, from_lane_index));
                 self.draw_car_only(
+                    stateless,
                     self.transform_to_lane_center(
                         transform,
                         city,
@@ -594,6 +631,7 @@ impl View {
                 let turn_heading = self.car_heading_offset_deb_to_turn(turn_direction);
                 let heading = origin_heading + turn_heading * proportion;
                 self.draw_car_only(
+                    stateless,
                     self.transform_to_intersection_center(transform, city, intersection_index)
                         .trans(x, y)
                         .rot_deg(heading),
@@ -605,15 +643,18 @@ impl View {
 
     /// Draw a car under centralized coordinate system.
     ///
-    /// The car is heading to north.
-    pub fn draw_car_only(&self, transform: Matrix2d, g2d: &mut G2d) {
-        let height = self.settings.car_length;
-        let width = self.settings.car_width;
-        let half_height = height / 2.0;
-        let half_width = width / 2.0;
+    /// The car is heading to north with its front bumper at the origin.
+    pub fn draw_car_only(&self, car: &stateless::Car, transform: Matrix2d, g2d: &mut G2d) {
+        let half_width = car.width / 2.0;
+        let color = match car.class {
+            VehicleClass::Car => self.settings.car_color,
+            VehicleClass::Bus => self.settings.bus_color,
+            VehicleClass::Truck => self.settings.truck_color,
+            VehicleClass::Motorcycle => self.settings.motorcycle_color,
+        };
         rectangle(
-            self.settings.car_color,
-            [-half_width, -half_height, width, height],
+            color,
+            [-half_width, 0.0, car.width, car.length],
             transform,
             g2d,
         );